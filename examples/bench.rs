@@ -0,0 +1,43 @@
+//! Time the computation of the graph of the sequences of the graphviz
+//! example, for each minimum length given on the command line
+//!
+//! Run with `cargo run --release --example bench -- 45 42 40`.
+
+use std::time::Instant;
+
+fn main() {
+    use xmcs::dag::xmcsk;
+
+    let s1 = "BDABCBADBCBADBADBDACDABCDABDCBACDBACDBCCDABCCCBADBCABDCABC"
+        .chars()
+        .collect::<Vec<_>>();
+    let s2 = "BDABCBACCBCBADBABDBDDACCBCDADCBAACDBADCDBCCDABCCBADBCCCADCDABC"
+        .chars()
+        .collect::<Vec<_>>();
+    let s3 = "BDABCBAABCBADADBADBDACBACDADCCBACDBACDBCBACDABCCABADBCADBACABC"
+        .chars()
+        .collect::<Vec<_>>();
+    let s4 = "BDABBACBABCCBADBABBDBDACBCDADCBDACDBACDBBCACDABACCBADCBCADCDBABC"
+        .chars()
+        .collect::<Vec<_>>();
+
+    let mut lens = std::env::args()
+        .skip(1)
+        .map(|arg| arg.parse::<usize>().expect("invalid length"))
+        .collect::<Vec<_>>();
+    if lens.is_empty() {
+        lens = vec![45, 42, 40];
+    }
+
+    for len in lens {
+        let start = Instant::now();
+        let res = xmcsk(len, &[&s1, &s2, &s3, &s4]);
+        let elapsed = start.elapsed();
+        println!(
+            "len {:>3}: {:>8.3}s, {} nodes",
+            len,
+            elapsed.as_secs_f64(),
+            res.stats().nodes
+        );
+    }
+}
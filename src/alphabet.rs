@@ -0,0 +1,360 @@
+//! Compression of sequences to dense symbol identifiers
+//!
+//! Comparing and hashing arbitrary elements can be much slower than
+//! comparing small integers. This module maps the distinct elements of
+//! a set of sequences to dense identifiers stored in a [`Symbol`] type
+//! (`u8`, `u16` or `u32`), so that the computations can be done on the
+//! encoded sequences and the results decoded back to the original
+//! values.
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::hash::Hash;
+
+use crate::dag::{Dag, Format};
+use crate::Error;
+
+/// Integer type used to store the identifier of a symbol
+pub trait Symbol: Copy + Eq + Hash {
+    /// Number of distinct identifiers representable by this type,
+    /// saturated to `usize::MAX`
+    fn capacity() -> usize;
+
+    /// Convert an identifier to a symbol
+    ///
+    /// `index` must be lower than [`capacity`](Symbol::capacity), larger
+    /// values are truncated.
+    fn from_index(index: usize) -> Self;
+
+    /// Identifier of the symbol
+    fn index(self) -> usize;
+}
+
+macro_rules! impl_symbol {
+    ($($t:ty),*) => {
+        $(
+            impl Symbol for $t {
+                fn capacity() -> usize {
+                    usize::try_from(<$t>::MAX).map_or(usize::MAX, |m| m.saturating_add(1))
+                }
+
+                #[allow(clippy::cast_possible_truncation)]
+                fn from_index(index: usize) -> Self {
+                    debug_assert!(index < Self::capacity());
+                    index as Self
+                }
+
+                #[allow(clippy::cast_possible_truncation)]
+                fn index(self) -> usize {
+                    self as usize
+                }
+            }
+        )*
+    };
+}
+
+impl_symbol!(u8, u16, u32);
+
+/// Bijection between the distinct elements of some sequences and the
+/// integers `0..len`
+///
+/// Identifiers are given in order of first occurrence.
+///
+/// # Examples
+/// ```
+/// # use xmcs::alphabet::Alphabet;
+/// let alphabet = Alphabet::new(&["CAB", "BAD"].map(str::as_bytes));
+///
+/// assert_eq!(4, alphabet.len());
+/// assert_eq!(Some(1), alphabet.id(&b'A'));
+/// assert_eq!(Some(vec![2u8, 3, 0]), alphabet.encode(b"BDC"));
+/// assert_eq!(b"DAB".to_vec(), alphabet.decode(&[3u8, 1, 2]));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Alphabet<T> {
+    /// Element corresponding to each identifier
+    symbols: Vec<T>,
+    /// Identifier of each element
+    ids: HashMap<T, usize>,
+}
+
+impl<T> Alphabet<T>
+where
+    T: Eq + Hash + Copy,
+{
+    /// Build the alphabet of the elements of `seqs`
+    pub fn new(seqs: &[&[T]]) -> Self {
+        let mut symbols = Vec::new();
+        let mut ids = HashMap::new();
+
+        for &e in seqs.iter().copied().flatten() {
+            ids.entry(e).or_insert_with(|| {
+                symbols.push(e);
+                symbols.len() - 1
+            });
+        }
+
+        Self { symbols, ids }
+    }
+
+    /// Number of distinct elements
+    pub fn len(&self) -> usize {
+        self.symbols.len()
+    }
+
+    /// Returns whether the alphabet is empty
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty()
+    }
+
+    /// Distinct elements, in order of their identifiers
+    pub fn symbols(&self) -> &[T] {
+        &self.symbols
+    }
+
+    /// Identifier of `symbol`, or `None` if it is not in the alphabet
+    pub fn id(&self, symbol: &T) -> Option<usize> {
+        self.ids.get(symbol).copied()
+    }
+
+    /// Element with identifier `id`, or `None` if there is none
+    pub fn symbol(&self, id: usize) -> Option<T> {
+        self.symbols.get(id).copied()
+    }
+
+    /// Encode `seq`, returns `None` if one of its elements is not in
+    /// the alphabet.
+    ///
+    /// # Panics
+    /// Panics if the alphabet has more elements than `S` can represent.
+    pub fn encode<S: Symbol>(&self, seq: &[T]) -> Option<Vec<S>> {
+        assert!(self.len() <= S::capacity());
+        seq.iter().map(|e| self.id(e).map(S::from_index)).collect()
+    }
+
+    /// Decode a sequence of identifiers
+    ///
+    /// # Panics
+    /// Panics if an identifier is not in the alphabet.
+    pub fn decode<S: Symbol>(&self, seq: &[S]) -> Vec<T> {
+        seq.iter().map(|s| self.symbols[s.index()]).collect()
+    }
+}
+
+/// Sequences encoded with their common alphabet
+///
+/// The computations are done on the encoded sequences, and the
+/// resulting subsequences are decoded with the alphabet.
+///
+/// # Examples
+/// ```
+/// # use xmcs::alphabet::Encoded;
+/// let seqs = [&["ab", "cd", "ef", "cd"][..], &["cd", "ab", "ef", "cd"]];
+/// let encoded = Encoded::<_, u8>::new(&seqs);
+///
+/// let dag = encoded.xmcsk(3);
+/// assert_eq!(Some(vec!["ab", "ef", "cd"]), encoded.extract_lcs(&dag));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Encoded<T, S> {
+    /// Common alphabet of the sequences
+    alphabet: Alphabet<T>,
+    /// Encoded sequences
+    sequences: Vec<Vec<S>>,
+}
+
+impl<T, S> Encoded<T, S>
+where
+    T: Eq + Hash + Copy,
+    S: Symbol,
+{
+    /// Encode `seqs` with their common alphabet
+    ///
+    /// # Panics
+    /// Panics if the sequences have more distinct elements than `S` can
+    /// represent.
+    pub fn new(seqs: &[&[T]]) -> Self {
+        match Self::try_new(seqs) {
+            Ok(res) => res,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Same as [`new`](Encoded::new), but returns an error if the
+    /// alphabet is too large.
+    ///
+    /// # Errors
+    /// Returns [`Error::AlphabetTooLarge`] if the sequences have more
+    /// distinct elements than `S` can represent.
+    pub fn try_new(seqs: &[&[T]]) -> Result<Self, Error> {
+        let alphabet = Alphabet::new(seqs);
+        if alphabet.len() > S::capacity() {
+            return Err(Error::AlphabetTooLarge {
+                symbols: alphabet.len(),
+                max: S::capacity(),
+            });
+        }
+
+        let sequences = seqs
+            .iter()
+            .map(|s| s.iter().map(|e| S::from_index(alphabet.ids[e])).collect())
+            .collect();
+
+        Ok(Self {
+            alphabet,
+            sequences,
+        })
+    }
+
+    /// Alphabet used to encode the sequences
+    pub fn alphabet(&self) -> &Alphabet<T> {
+        &self.alphabet
+    }
+
+    /// Encoded sequences
+    pub fn sequences(&self) -> Vec<&[S]> {
+        self.sequences.iter().map(Vec::as_slice).collect()
+    }
+
+    /// Compute the graph of an extended set of maximal common
+    /// subsequences of length at least `len` of the encoded sequences
+    ///
+    /// The encoded symbols are hashable, so the automaton of each
+    /// sequence added to the graph is used to avoid expanding the End
+    /// nodes whose suffix is a subsequence of the rest of the sequence.
+    ///
+    /// See [`dag::xmcsk`](crate::dag::xmcsk).
+    pub fn xmcsk(&self, len: usize) -> Dag<'_, S> {
+        Dag::xmcsk_with_automaton(len, &self.sequences())
+    }
+
+    /// Same as [`xmcsk`](Encoded::xmcsk), but returns an error if the
+    /// parameters are invalid.
+    ///
+    /// # Errors
+    /// See [`dag::try_xmcsk`](crate::dag::try_xmcsk).
+    pub fn try_xmcsk(&self, len: usize) -> Result<Dag<'_, S>, Error> {
+        Dag::try_xmcsk_with_automaton(len, &self.sequences())
+    }
+
+    /// Decode a sequence of identifiers
+    ///
+    /// # Panics
+    /// Panics if an identifier is not in the alphabet.
+    pub fn decode(&self, seq: &[S]) -> Vec<T> {
+        self.alphabet.decode(seq)
+    }
+
+    /// Extract and decode one of the longest subsequences of `dag`
+    ///
+    /// See [`Dag::extract_lcs`].
+    pub fn extract_lcs(&self, dag: &Dag<'_, S>) -> Option<Vec<T>> {
+        dag.extract_lcs().map(|s| self.decode(&s))
+    }
+
+    /// Enumerate and decode all the sequences represented by `dag`
+    ///
+    /// See [`Dag::to_set`].
+    pub fn to_set(&self, dag: &Dag<'_, S>) -> HashSet<Vec<T>> {
+        dag.to_set().iter().map(|s| self.decode(s)).collect()
+    }
+}
+
+impl<T, S> Encoded<T, S>
+where
+    T: std::fmt::Display,
+    S: Symbol,
+{
+    /// Writes `dag` into `w` in the given `format`, labelled with the
+    /// original elements
+    ///
+    /// See [`Dag::export`].
+    ///
+    /// # Errors
+    /// Forwards errors from writing into `w`.
+    pub fn export(
+        &self,
+        dag: &Dag<'_, S>,
+        format: Format,
+        w: &mut impl std::io::Write,
+    ) -> std::io::Result<()> {
+        dag.export_with(format, w, |s| self.alphabet.symbols[s.index()].to_string())
+    }
+
+    /// Outputs code to be used with the [dot] tool to produce a
+    /// visualisation of `dag`, labelled with the original elements
+    ///
+    /// # Errors
+    /// Forwards errors from writing into `w`.
+    ///
+    /// [dot]: (https://graphviz.org/)
+    pub fn format_graph(
+        &self,
+        dag: &Dag<'_, S>,
+        w: &mut impl std::io::Write,
+    ) -> std::io::Result<()> {
+        dag.format_graph_with(w, |s| self.alphabet.symbols[s.index()].to_string())
+    }
+}
+
+// === Tests ===
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_alphabet() {
+        let alphabet = Alphabet::new(&[&[30, 10, 30], &[], &[20, 10]]);
+
+        assert_eq!(&[30, 10, 20], alphabet.symbols());
+        assert_eq!(None, alphabet.id(&40));
+        assert_eq!(Some(20), alphabet.symbol(2));
+        assert_eq!(None, alphabet.symbol(3));
+
+        assert_eq!(Some(vec![1u16, 0, 2]), alphabet.encode(&[10, 30, 20]));
+        assert_eq!(None, alphabet.encode::<u16>(&[10, 40]));
+        assert_eq!(vec![20, 20, 30], alphabet.decode(&[2u32, 2, 0]));
+
+        let empty = Alphabet::<u8>::new(&[]);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_too_large() {
+        let seq = (0..300).collect::<Vec<u32>>();
+
+        assert_eq!(
+            Err(Error::AlphabetTooLarge {
+                symbols: 300,
+                max: 256
+            }),
+            Encoded::<_, u8>::try_new(&[&seq]).map(|_| ())
+        );
+        assert!(Encoded::<_, u16>::try_new(&[&seq]).is_ok());
+    }
+
+    #[test]
+    fn test_encoded_xmcsk() {
+        let seqs = [
+            "the quick brown fox jumps over the lazy dog".as_bytes(),
+            "a quick brown dog jumps over the lazy fox".as_bytes(),
+            "the brown fox quickly jumps over a dog".as_bytes(),
+        ];
+        let words = seqs
+            .iter()
+            .map(|s| s.split(|&c| c == b' ').collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let words = words.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+        let encoded = Encoded::<_, u8>::new(&words);
+        let dag = encoded.xmcsk(2);
+        let expected = crate::dag::xmcsk(2, &words);
+
+        assert_eq!(expected.to_set(), encoded.to_set(&dag));
+        assert_eq!(
+            expected.extract_lcs().map(|s| s.len()),
+            encoded.extract_lcs(&dag).map(|s| s.len())
+        );
+    }
+}
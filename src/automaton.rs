@@ -0,0 +1,238 @@
+//! Subsequence automaton of a sequence
+//!
+//! This module contains a structure storing, for each position of a
+//! sequence and each symbol, the next position where this symbol occurs.
+//! It is used to check in time linear in the length of a sequence
+//! whether it is a subsequence of the sequence used at construction,
+//! and to compute its leftmost embedding.
+//!
+//! [`dag::xmcsk`] builds the automaton of each sequence it adds to the
+//! graph, when its table is small enough, to find the End nodes whose
+//! suffix is a subsequence of the rest of the sequence without building
+//! a [`SubString`] table for them. [`bounds`] uses it to compute a common
+//! subsequence greedily.
+//!
+//! [`dag::xmcsk`]: crate::dag::xmcsk
+//! [`SubString`]: crate::substr::SubString
+//! [`bounds`]: crate::bounds
+
+use std::convert::TryFrom;
+use std::hash::Hash;
+use std::mem::size_of;
+
+use crate::alphabet::Alphabet;
+
+/// Next-occurrence automaton of a sequence
+///
+/// The symbols of the sequence are mapped to dense identifiers, and the
+/// automaton stores a table of `(|s| + 1) * σ` 32-bit positions where
+/// `σ` is the number of distinct symbols of `s`. It is built in time
+/// `O(|s| * σ)`: use [`with_limit`](Automaton::with_limit) when `σ` may
+/// be large.
+///
+/// # Examples
+/// ```
+/// # use xmcs::automaton::Automaton;
+/// let automaton = Automaton::new(b"ABCABD");
+///
+/// assert_eq!(Some(3), automaton.next(1, &b'A'));
+/// assert_eq!(None, automaton.next(3, &b'C'));
+///
+/// assert_eq!(true, automaton.is_subsequence(b"AAD"));
+/// assert_eq!(false, automaton.is_subsequence(b"DA"));
+/// assert_eq!(Some(vec![0, 2, 4]), automaton.embedding(b"ACB"));
+/// ```
+pub struct Automaton<T> {
+    /// Identifier of each symbol of the sequence
+    alphabet: Alphabet<T>,
+    /// Length of the sequence
+    len: usize,
+    /// `next[p * σ + c]` is the first position `q >= p` such that
+    /// the symbol at position `q` has identifier `c`, or `len` if
+    /// there is none.
+    next: Vec<u32>,
+}
+
+impl<T> Automaton<T> {
+    /// Estimated number of bytes allocated for the table, not counting
+    /// the alphabet
+    pub fn bytes(&self) -> usize {
+        self.next.capacity() * size_of::<u32>()
+    }
+
+    /// Length of the sequence used at construction
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the sequence used at construction is empty
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T> Automaton<T>
+where
+    T: Eq + Hash + Copy,
+{
+    /// Build the automaton of `seq`
+    ///
+    /// # Panics
+    /// Panics if `seq` has `2^32 - 1` elements or more.
+    pub fn new(seq: &[T]) -> Self {
+        Self::with_limit(seq, usize::MAX).expect("sequence too long for the automaton")
+    }
+
+    /// Build the automaton of `seq`, unless its table would have more
+    /// than `max_entries` entries or `seq` has `2^32 - 1` elements or
+    /// more
+    ///
+    /// # Examples
+    /// ```
+    /// # use xmcs::automaton::Automaton;
+    /// // 6 positions and 3 symbols
+    /// assert!(Automaton::with_limit(b"ABCAB", 18).is_some());
+    /// assert!(Automaton::with_limit(b"ABCAB", 17).is_none());
+    /// ```
+    pub fn with_limit(seq: &[T], max_entries: usize) -> Option<Self> {
+        let alphabet = Alphabet::new(&[seq]);
+
+        let sigma = alphabet.len();
+        let len = u32::try_from(seq.len()).ok().filter(|&l| l < u32::MAX)?;
+        let size = (seq.len() + 1).checked_mul(sigma)?;
+        if size > max_entries {
+            return None;
+        }
+
+        let mut next = vec![len; size];
+        for (p, e) in (0..len).zip(seq).rev() {
+            let (row, rest) = next[p as usize * sigma..].split_at_mut(sigma);
+            row.copy_from_slice(&rest[..sigma]);
+            if let Some(id) = alphabet.id(e) {
+                row[id] = p;
+            }
+        }
+
+        Some(Self {
+            alphabet,
+            len: seq.len(),
+            next,
+        })
+    }
+
+    /// Returns the first position greater or equal to `pos` where
+    /// `symbol` occurs, or `None` if there is none.
+    ///
+    /// Runs in constant time.
+    ///
+    /// # Panics
+    /// Panics if `pos` is larger than the length of the sequence.
+    pub fn next(&self, pos: usize, symbol: &T) -> Option<usize> {
+        assert!(pos <= self.len);
+
+        let id = self.alphabet.id(symbol)?;
+        let next = self.next[pos * self.alphabet.len() + id] as usize;
+        if next < self.len {
+            Some(next)
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether `seq` is a subsequence of the sequence used at
+    /// construction.
+    ///
+    /// Runs in time `O(|seq|)`.
+    pub fn is_subsequence(&self, seq: &[T]) -> bool {
+        self.is_subsequence_from(0, seq)
+    }
+
+    /// Returns whether `seq` is a subsequence of the tail starting at
+    /// position `pos` of the sequence used at construction.
+    ///
+    /// Runs in time `O(|seq|)`.
+    ///
+    /// # Panics
+    /// Panics if `pos` is larger than the length of the sequence.
+    pub fn is_subsequence_from(&self, pos: usize, seq: &[T]) -> bool {
+        self.embedding_from(pos, seq).is_some()
+    }
+
+    /// Returns the positions of the leftmost embedding of `seq` in
+    /// the sequence used at construction, or `None` if `seq` is not a
+    /// subsequence.
+    ///
+    /// Runs in time `O(|seq|)`.
+    pub fn embedding(&self, seq: &[T]) -> Option<Vec<usize>> {
+        self.embedding_from(0, seq)
+    }
+
+    /// Returns the positions of the leftmost embedding of `seq` in the
+    /// tail starting at position `pos` of the sequence used at
+    /// construction, or `None` if `seq` is not a subsequence of it.
+    ///
+    /// Runs in time `O(|seq|)`.
+    ///
+    /// # Panics
+    /// Panics if `pos` is larger than the length of the sequence.
+    pub fn embedding_from(&self, mut pos: usize, seq: &[T]) -> Option<Vec<usize>> {
+        let mut res = Vec::with_capacity(seq.len());
+
+        for e in seq {
+            if pos >= self.len {
+                return None;
+            }
+
+            let next = self.next(pos, e)?;
+            res.push(next);
+            pos = next + 1;
+        }
+
+        Some(res)
+    }
+}
+
+// === Tests ===
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_next() {
+        let seq = b"ACBBABCBCACBACBCBBABBCAC";
+        let automaton = Automaton::new(seq);
+
+        for pos in 0..=seq.len() {
+            for symbol in b"ABCD" {
+                let expected = seq[pos..].iter().position(|e| e == symbol).map(|p| p + pos);
+                assert_eq!(expected, automaton.next(pos, symbol));
+            }
+        }
+    }
+
+    #[test]
+    fn test_subsequence() {
+        let automaton = Automaton::new(b"BCABCDAABCD");
+
+        assert!(automaton.is_subsequence(b""));
+        assert!(automaton.is_subsequence(b"BCABCDAABCD"));
+        assert!(automaton.is_subsequence(b"BBBD"));
+        assert!(!automaton.is_subsequence(b"BBBDD"));
+        assert!(!automaton.is_subsequence(b"BBBB"));
+        assert!(!automaton.is_subsequence(b"AE"));
+
+        assert!(automaton.is_subsequence_from(7, b"ABCD"));
+        assert!(!automaton.is_subsequence_from(8, b"ABCD"));
+        assert!(automaton.is_subsequence_from(11, b""));
+
+        assert_eq!(Some(vec![2, 6, 7, 10]), automaton.embedding(b"AAAD"));
+        assert_eq!(Some(vec![8, 10]), automaton.embedding_from(6, b"BD"));
+        assert_eq!(None, automaton.embedding_from(6, b"BDD"));
+
+        let empty = Automaton::<u8>::new(b"");
+        assert!(empty.is_empty());
+        assert!(empty.is_subsequence(b""));
+        assert!(!empty.is_subsequence(b"A"));
+    }
+}
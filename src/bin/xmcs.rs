@@ -0,0 +1,498 @@
+//! Command line interface to compute an extended set of maximal common
+//! subsequences of sequences read from files.
+
+use std::fmt::Display;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::process;
+use std::time::{Duration, Instant};
+
+use std::collections::HashSet;
+
+use xmcs::dag::{Budget, Control, Format, Progress, Stats};
+use xmcs::io::SoftMask;
+
+const USAGE: &str = "\
+Usage: xmcs --len <LEN> [OPTIONS] [FILE]...
+
+Compute an extended set of maximal common subsequences of length at
+least LEN of all the sequences read from the given files. Reads from the
+standard input if no file is given or if FILE is `-`.
+
+Files contain either one sequence per line, FASTA records or FASTQ
+records.
+
+Options:
+    -l, --len <LEN>         Minimum length of the subsequences
+    -g, --gap <GAP>         Only keep subsequences whose consecutive elements
+                            are at most GAP positions apart in every input
+                            (`dag` mode only)
+    -m, --mode <MODE>       Backend used for the computation: `dag` (default)
+                            or `set`
+    -o, --output <OUTPUT>   What to print on the standard output: `lcs`
+                            (default), `all`, `count`, and in `dag` mode
+                            `paths` or the graph as `dot`, `json`,
+                            `graphml` or `mermaid`
+    --max-nodes <N>         Stop if the graph has more than N nodes
+                            (`dag` mode only)
+    --max-bytes <N>         Stop if the computation uses more than about
+                            N bytes (`dag` mode only)
+    -u, --unmask            Convert lowercase (soft-masked) letters to
+                            uppercase
+    -q, --quiet             Do not print timing and statistics
+    -h, --help              Print this message
+
+`count` prints the number of distinct subsequences. In `dag` mode, `all`
+follows the paths of the graph without storing the subsequences, so a
+subsequence spelled by several paths is printed once for each of them,
+and `paths` prints the number of paths without enumerating them.
+
+Timing and statistics are printed on the standard error.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Set,
+    Dag,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Lcs,
+    All,
+    Count,
+    Paths,
+    Export(Format),
+}
+
+struct Options {
+    len: usize,
+    gap: Option<usize>,
+    mode: Mode,
+    output: Output,
+    mask: SoftMask,
+    budget: Budget,
+    quiet: bool,
+    files: Vec<String>,
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(msg) => {
+            eprintln!("error: {}\n\n{}", msg, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if let Err(msg) = run(&options) {
+        eprintln!("error: {}", msg);
+        process::exit(1);
+    }
+}
+
+/// Parse the command line arguments, returns `None` if the help
+/// message was requested.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut len = None;
+    let mut gap = None;
+    let mut mode = Mode::Dag;
+    let mut output = Output::Lcs;
+    let mut mask = SoftMask::Keep;
+    let mut budget = Budget::default();
+    let mut quiet = false;
+    let mut files = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("missing value for `{}`", name))
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-q" | "--quiet" => quiet = true,
+            "-u" | "--unmask" => mask = SoftMask::Unmask,
+            "-l" | "--len" => {
+                let v = value(&arg)?;
+                let v = v.parse().map_err(|_| format!("invalid length `{}`", v))?;
+                len = Some(v);
+            }
+            "-g" | "--gap" => {
+                let v = value(&arg)?;
+                let v = v.parse().map_err(|_| format!("invalid gap `{}`", v))?;
+                gap = Some(v);
+            }
+            "--max-nodes" => {
+                let v = value(&arg)?;
+                let v = v.parse().map_err(|_| format!("invalid limit `{}`", v))?;
+                budget.max_nodes = Some(v);
+            }
+            "--max-bytes" => {
+                let v = value(&arg)?;
+                let v = v.parse().map_err(|_| format!("invalid limit `{}`", v))?;
+                budget.max_bytes = Some(v);
+            }
+            "-m" | "--mode" => {
+                mode = match value(&arg)?.as_str() {
+                    "set" => Mode::Set,
+                    "dag" => Mode::Dag,
+                    v => return Err(format!("invalid mode `{}`", v)),
+                };
+            }
+            "-o" | "--output" => {
+                output = match value(&arg)?.as_str() {
+                    "lcs" => Output::Lcs,
+                    "all" => Output::All,
+                    "count" => Output::Count,
+                    "paths" => Output::Paths,
+                    "dot" => Output::Export(Format::Dot),
+                    "json" => Output::Export(Format::Json),
+                    "graphml" => Output::Export(Format::GraphMl),
+                    "mermaid" => Output::Export(Format::Mermaid),
+                    v => return Err(format!("invalid output `{}`", v)),
+                };
+            }
+            "-" => files.push(arg),
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+            _ => files.push(arg),
+        }
+    }
+
+    let len = len.ok_or_else(|| String::from("missing `--len`"))?;
+
+    if gap.is_some() && mode == Mode::Set {
+        return Err(String::from("`--gap` is only available in `dag` mode"));
+    }
+
+    if budget != Budget::default() && mode == Mode::Set {
+        return Err(String::from(
+            "`--max-nodes` and `--max-bytes` are only available in `dag` mode",
+        ));
+    }
+
+    if matches!(output, Output::Paths | Output::Export(_)) && mode == Mode::Set {
+        return Err(String::from(
+            "`paths` and graph outputs are only available in `dag` mode",
+        ));
+    }
+
+    if files.is_empty() {
+        files.push(String::from("-"));
+    }
+
+    Ok(Some(Options {
+        len,
+        gap,
+        mode,
+        output,
+        mask,
+        budget,
+        quiet,
+        files,
+    }))
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let mut sequences = Vec::new();
+    for file in &options.files {
+        let content = read_file(file).map_err(|e| format!("cannot read `{}`: {}", file, e))?;
+        sequences.extend(parse_sequences(file, &content, options.mask)?);
+    }
+
+    if sequences.is_empty() {
+        return Err(String::from("no sequence found in the input"));
+    }
+
+    let seqs = sequences
+        .iter()
+        .map(|(_, s)| s.as_slice())
+        .collect::<Vec<_>>();
+
+    if !options.quiet {
+        eprintln!("sequences: {}", seqs.len());
+        for (name, seq) in &sequences {
+            eprintln!("\t{}: length {}", name, seq.len());
+        }
+    }
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    match options.mode {
+        Mode::Set => {
+            let (set, elapsed) = timed(|| xmcs::set::try_xmcsk(options.len, &seqs));
+            let set = set.map_err(|e| e.to_string())?;
+
+            if !options.quiet {
+                eprintln!("time: {}", format_duration(elapsed));
+                eprintln!("subsequences: {}", set.len());
+            }
+
+            match options.output {
+                Output::Lcs => {
+                    if let Some(lcs) = set.iter().max_by_key(|s| s.len()) {
+                        write_seq(&mut out, lcs)
+                    } else {
+                        Ok(())
+                    }
+                }
+                Output::All => set.iter().try_for_each(|s| write_seq(&mut out, s)),
+                Output::Count => writeln!(out, "{}", set.len()),
+                Output::Paths | Output::Export(_) => unreachable!(),
+            }
+        }
+        Mode::Dag => {
+            let (dag, elapsed) = timed(|| {
+                let mut observer = |p: &Progress| report_progress(p, options.quiet);
+                match options.gap {
+                    Some(gap) => xmcs::dag::try_xmcsk_with_gap_observed(
+                        options.len,
+                        gap,
+                        &seqs,
+                        &options.budget,
+                        &mut observer,
+                    ),
+                    None => xmcs::dag::try_xmcsk_observed(
+                        options.len,
+                        &seqs,
+                        &options.budget,
+                        &mut observer,
+                    ),
+                }
+            });
+            let dag = dag.map_err(|e| e.to_string())?;
+
+            if !options.quiet {
+                eprintln!("time: {}", format_duration(elapsed));
+                print_stats(&dag.stats());
+            }
+
+            match options.output {
+                Output::Lcs => match dag.extract_lcs() {
+                    Some(lcs) => write_seq(&mut out, &lcs),
+                    None => Ok(()),
+                },
+                Output::All => dag.for_each_sequence(|s| write_seq(&mut out, s)),
+                Output::Count => {
+                    let mut set = HashSet::new();
+                    dag.for_each_sequence(|s| -> io::Result<()> {
+                        set.insert(s.to_vec());
+                        Ok(())
+                    })
+                    .and_then(|()| writeln!(out, "{}", set.len()))
+                }
+                Output::Paths => writeln!(out, "{}", dag.path_count()),
+                Output::Export(format) => dag.export(format, &mut out),
+            }
+        }
+    }
+    .map_err(|e| e.to_string())
+}
+
+fn read_file(name: &str) -> io::Result<Vec<u8>> {
+    if name == "-" {
+        let mut content = Vec::new();
+        io::stdin().read_to_end(&mut content)?;
+        Ok(content)
+    } else {
+        fs::read(name)
+    }
+}
+
+/// Parse the content of a file, either as FASTA or FASTQ records if it
+/// starts with `>` or `@` or as one sequence per line otherwise.
+fn parse_sequences(
+    file: &str,
+    content: &[u8],
+    mask: SoftMask,
+) -> Result<Vec<(String, Vec<char>)>, String> {
+    let to_chars = |seq: &[u8]| seq.iter().copied().map(char::from).collect::<Vec<_>>();
+
+    let first = content.iter().find(|c| !c.is_ascii_whitespace());
+    if let Some(b'>' | b'@') = first {
+        let records =
+            xmcs::io::read(content, mask).map_err(|e| format!("invalid file `{}`: {}", file, e))?;
+        Ok(records
+            .into_iter()
+            .map(|r| (r.name, to_chars(&r.seq)))
+            .collect())
+    } else {
+        let content = String::from_utf8_lossy(content);
+        Ok(content
+            .lines()
+            .map(str::trim)
+            .enumerate()
+            .filter(|(_, l)| !l.is_empty())
+            .map(|(i, l)| {
+                let name = format!("{}:{}", file, i + 1);
+                match mask {
+                    SoftMask::Keep => (name, l.chars().collect()),
+                    SoftMask::Unmask => (name, l.to_uppercase().chars().collect()),
+                }
+            })
+            .collect())
+    }
+}
+
+/// Print the number of nodes once each sequence is added, unless
+/// `quiet` is set
+fn report_progress(progress: &Progress, quiet: bool) -> Control {
+    if progress.added && !quiet {
+        eprintln!(
+            "added sequence {}/{}: {} nodes",
+            progress.sequence + 1,
+            progress.sequences,
+            progress.nodes
+        );
+    }
+    Control::Continue
+}
+
+/// Print the statistics of the graph on the standard error
+fn print_stats(stats: &Stats) {
+    eprintln!("nodes: {} ({} reachable)", stats.nodes, stats.reachable);
+    eprintln!(
+        "\tsplit: {}, element: {}, end: {}, empty: {}",
+        stats.split, stats.element, stats.end, stats.empty
+    );
+    eprintln!("\tdistinct suffix slices: {}", stats.distinct_suffix_slices);
+    eprintln!("depth: {}", stats.depth);
+    eprintln!("shared: {:.1}%", stats.sharing * 100.);
+    eprintln!("bytes: {}", stats.bytes);
+}
+
+fn timed<R>(f: impl FnOnce() -> R) -> (R, Duration) {
+    let start = Instant::now();
+    let res = f();
+    (res, start.elapsed())
+}
+
+fn format_duration(d: Duration) -> String {
+    format!("{:.3}s", d.as_secs_f64())
+}
+
+fn write_seq(w: &mut impl Write, seq: &[impl Display]) -> io::Result<()> {
+    for e in seq {
+        write!(w, "{}", e)?;
+    }
+    writeln!(w)
+}
+
+// === Tests ===
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Option<Options>, String> {
+        parse_args(args.split_whitespace().map(String::from))
+    }
+
+    fn error(args: &str) -> String {
+        parse(args).err().expect("the arguments should be rejected")
+    }
+
+    fn seqs(content: &str, mask: SoftMask) -> Vec<(String, String)> {
+        parse_sequences("in", content.as_bytes(), mask)
+            .unwrap()
+            .into_iter()
+            .map(|(name, seq)| (name, seq.into_iter().collect()))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse("--len 3").unwrap().unwrap();
+        assert_eq!(3, options.len);
+        assert_eq!(None, options.gap);
+        assert_eq!(Mode::Dag, options.mode);
+        assert_eq!(Output::Lcs, options.output);
+        assert_eq!(SoftMask::Keep, options.mask);
+        assert_eq!(Budget::default(), options.budget);
+        assert!(!options.quiet);
+        assert_eq!(vec!["-"], options.files);
+
+        let options = parse("a.txt -l 5 -g 2 -o dot --max-nodes 10 -u -q -")
+            .unwrap()
+            .unwrap();
+        assert_eq!(5, options.len);
+        assert_eq!(Some(2), options.gap);
+        assert_eq!(Output::Export(Format::Dot), options.output);
+        assert_eq!(Some(10), options.budget.max_nodes);
+        assert_eq!(SoftMask::Unmask, options.mask);
+        assert!(options.quiet);
+        assert_eq!(vec!["a.txt", "-"], options.files);
+
+        let options = parse("-l 1 -m set -o count").unwrap().unwrap();
+        assert_eq!(Mode::Set, options.mode);
+        assert_eq!(Output::Count, options.output);
+
+        assert!(parse("-l 1 --help").unwrap().is_none());
+        assert!(parse("-h --unknown").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_args_errors() {
+        assert_eq!("unknown option `--unknown`", error("-l 1 --unknown"));
+        assert_eq!("missing value for `--len`", error("--len"));
+        assert_eq!("missing value for `-o`", error("-l 1 -o"));
+        assert_eq!("missing `--len`", error("a.txt"));
+        assert_eq!("invalid length `x`", error("-l x"));
+        assert_eq!("invalid gap `-1`", error("-l 1 -g -1"));
+        assert_eq!("invalid limit `1k`", error("-l 1 --max-bytes 1k"));
+        assert_eq!("invalid mode `tree`", error("-l 1 -m tree"));
+        assert_eq!("invalid output `svg`", error("-l 1 -o svg"));
+
+        // Options of the `dag` mode only
+        for args in [
+            "-m set -l 1 -g 1",
+            "-m set -l 1 --max-nodes 1",
+            "-m set -l 1 -o paths",
+            "-m set -l 1 -o json",
+        ] {
+            assert!(error(args).contains("only available in `dag` mode"));
+        }
+    }
+
+    #[test]
+    fn test_parse_sequences() {
+        let lines = "ACGT\n\n  acgt  \r\nAC\n";
+        assert_eq!(
+            vec![
+                (String::from("in:1"), String::from("ACGT")),
+                (String::from("in:3"), String::from("acgt")),
+                (String::from("in:4"), String::from("AC")),
+            ],
+            seqs(lines, SoftMask::Keep)
+        );
+        assert_eq!("ACGT", seqs(lines, SoftMask::Unmask)[1].1);
+
+        let fasta = "\n>s1 first\nACG\nTac\n>s2\nGG\n";
+        assert_eq!(
+            vec![
+                (String::from("s1"), String::from("ACGTac")),
+                (String::from("s2"), String::from("GG")),
+            ],
+            seqs(fasta, SoftMask::Keep)
+        );
+        assert_eq!("ACGTAC", seqs(fasta, SoftMask::Unmask)[0].1);
+
+        let fastq = "@r1\nACGT\n+\nIIII\n@r2\nTT\n+r2\nII\n";
+        assert_eq!(
+            vec![
+                (String::from("r1"), String::from("ACGT")),
+                (String::from("r2"), String::from("TT")),
+            ],
+            seqs(fastq, SoftMask::Keep)
+        );
+
+        assert!(seqs("", SoftMask::Keep).is_empty());
+
+        let err = parse_sequences("in", b"@r1\nACGT\n+\nII\n", SoftMask::Keep).unwrap_err();
+        assert!(err.starts_with("invalid file `in`"));
+    }
+}
@@ -0,0 +1,251 @@
+//! Bounds on the length of a longest common subsequence
+//!
+//! Computing an extended set of maximal common subsequences is costly,
+//! and useless if `len` is larger than the length of a longest common
+//! subsequence. This module computes cheap bounds on this length:
+//! - an upper bound, from the lengths of the longest common subsequences
+//!   of all the pairs of sequences and from the number of occurrences
+//!   of each symbol,
+//! - a lower bound, from a common subsequence built greedily.
+
+use std::cmp::min;
+use std::hash::Hash;
+
+use crate::alphabet::Alphabet;
+use crate::lcs::lcs_length;
+use crate::Error;
+
+/// Bounds on the length of a longest common subsequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    /// Length of a common subsequence
+    pub lower: usize,
+    /// No common subsequence is longer than this
+    pub upper: usize,
+}
+
+/// Compute bounds on the length of a longest common subsequence of
+/// `seqs`
+///
+/// # Examples
+/// ```
+/// # use xmcs::bounds::{bounds, Bounds};
+/// let seqs = [&b"ADBCBAD"[..], b"ADCBACD", b"ABDCABDA"];
+/// let Bounds { lower, upper } = bounds(&seqs);
+///
+/// assert!(lower <= 5 && 5 <= upper);
+/// ```
+pub fn bounds<T>(seqs: &[&[T]]) -> Bounds
+where
+    T: Eq + Hash + Copy,
+{
+    Bounds {
+        lower: greedy(seqs).len(),
+        upper: upper_bound(seqs),
+    }
+}
+
+/// Compute an upper bound on the length of a longest common subsequence
+/// of `seqs`
+///
+/// The bound is the minimum of the lengths of the longest common
+/// subsequences of all the pairs of sequences, and of the sum over all
+/// the symbols of their minimum number of occurrences in a sequence.
+/// It only requires `T: Eq`, and runs in time `O(k² n (n / 64 + σ))`
+/// for `k` sequences of length at most `n` with `σ` distinct elements.
+pub fn upper_bound<T: Eq>(seqs: &[&[T]]) -> usize {
+    let Some(shortest) = seqs.iter().min_by_key(|s| s.len()) else {
+        return 0;
+    };
+
+    // Distinct symbols of the shortest sequence, the other symbols
+    // cannot occur in a common subsequence
+    let mut symbols = Vec::<&T>::new();
+    for e in *shortest {
+        if !symbols.contains(&e) {
+            symbols.push(e);
+        }
+    }
+
+    let mut counts = vec![usize::MAX; symbols.len()];
+    for s in seqs {
+        let mut current = vec![0; symbols.len()];
+        for e in *s {
+            if let Some(id) = symbols.iter().position(|&x| x == e) {
+                current[id] += 1;
+            }
+        }
+        for (count, c) in counts.iter_mut().zip(current) {
+            *count = min(*count, c);
+        }
+    }
+
+    let mut res = counts.iter().sum();
+    for (i, s1) in seqs.iter().enumerate() {
+        for s2 in &seqs[i + 1..] {
+            if res == 0 {
+                return 0;
+            }
+            res = min(res, lcs_length(s1, s2));
+        }
+    }
+
+    res
+}
+
+/// Check that the sequences may have a common subsequence of length
+/// `len`, using [`upper_bound`]
+///
+/// The `try_` functions run this check after checking the lengths of the
+/// sequences. It costs as much as [`upper_bound`], which computes the
+/// longest common subsequence of all the pairs of sequences, far less
+/// than the computations it avoids when `len` is too large. It runs
+/// before the [`Budget`] and the [`Observer`] of the `try_` functions
+/// apply, and is skipped when `len` is 0.
+///
+/// # Errors
+/// Returns [`Error::LengthTooLarge`] if `len` is larger than
+/// [`upper_bound`].
+///
+/// # Examples
+/// ```
+/// # use xmcs::bounds::check_upper_bound;
+/// # use xmcs::Error;
+/// let seqs = [&b"ABCD"[..], b"DCBA", b"ABDC"];
+///
+/// // The sequences are long enough, but have no common subsequence of
+/// // length 2
+/// let err = check_upper_bound(2, &seqs);
+/// assert_eq!(Err(Error::LengthTooLarge { len: 2, max: 1 }), err);
+/// assert_eq!(err.err(), xmcs::dag::try_xmcsk(2, &seqs).err());
+/// ```
+///
+/// [`Budget`]: crate::dag::Budget
+/// [`Observer`]: crate::dag::Observer
+pub fn check_upper_bound<T: Eq>(len: usize, seqs: &[&[T]]) -> Result<(), Error> {
+    if len == 0 || seqs.is_empty() {
+        return Ok(());
+    }
+
+    match upper_bound(seqs) {
+        max if max < len => Err(Error::LengthTooLarge { len, max }),
+        _ => Ok(()),
+    }
+}
+
+/// Compute a common subsequence of `seqs` greedily
+///
+/// At each step, the symbol appended is the one whose next occurrences
+/// leave the longest remaining tails, that is which maximises the length
+/// of the shortest remaining tail. The length of the result is a lower
+/// bound on the length of a longest common subsequence.
+///
+/// Runs in time `O(k n σ log n)` and uses `O(k (n + σ))` memory for `k`
+/// sequences of length at most `n` with `σ` distinct elements.
+pub fn greedy<T>(seqs: &[&[T]]) -> Vec<T>
+where
+    T: Eq + Hash + Copy,
+{
+    let alphabet = Alphabet::new(seqs);
+    // Positions of each symbol in each sequence, in increasing order, the
+    // next occurrence of a symbol is found by binary search
+    let occurrences = seqs
+        .iter()
+        .map(|s| {
+            let mut occurrences = vec![Vec::new(); alphabet.len()];
+            for (i, e) in s.iter().enumerate() {
+                if let Some(id) = alphabet.id(e) {
+                    occurrences[id].push(i);
+                }
+            }
+            occurrences
+        })
+        .collect::<Vec<_>>();
+
+    let mut positions = vec![0; seqs.len()];
+    let mut res = Vec::new();
+
+    if seqs.is_empty() {
+        return res;
+    }
+
+    loop {
+        let best = alphabet
+            .symbols()
+            .iter()
+            .enumerate()
+            .filter_map(|(id, e)| {
+                let next = occurrences
+                    .iter()
+                    .zip(&positions)
+                    .map(|(occurrences, &pos)| {
+                        let occurrences = &occurrences[id];
+                        let i = occurrences.partition_point(|&p| p < pos);
+                        occurrences.get(i).copied()
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                let remaining = next.iter().zip(seqs).map(|(&p, s)| s.len() - p - 1).min()?;
+                Some((remaining, e, next))
+            })
+            .max_by_key(|&(remaining, ..)| remaining);
+
+        match best {
+            Some((_, &e, next)) => {
+                res.push(e);
+                for (pos, p) in positions.iter_mut().zip(next) {
+                    *pos = p + 1;
+                }
+            }
+            None => return res,
+        }
+    }
+}
+
+// === Tests ===
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::automaton::Automaton;
+    use crate::reference::{self, random_sequences};
+
+    #[test]
+    fn test_bounds() {
+        assert_eq!(Bounds { lower: 0, upper: 0 }, bounds::<u8>(&[]));
+        assert_eq!(Bounds { lower: 3, upper: 3 }, bounds(&[b"ABC"]));
+        assert_eq!(
+            Bounds { lower: 0, upper: 0 },
+            bounds(&[&b"ABC"[..], b"DEF"])
+        );
+
+        // Each symbol is missing from one of the sequences
+        assert_eq!(0, upper_bound(&[&b"CA"[..], b"AB", b"BC"]));
+        // Bounded by the longest common subsequence of a pair
+        assert_eq!(1, upper_bound(&[&b"ABC"[..], b"CBA", b"ABC"]));
+
+        let seqs = [&b"ABCD"[..], b"DCBA", b"ABDC"];
+        assert_eq!(Ok(()), check_upper_bound(1, &seqs));
+        let err = check_upper_bound(3, &seqs);
+        assert_eq!(Err(Error::LengthTooLarge { len: 3, max: 1 }), err);
+        assert_eq!(Ok(()), check_upper_bound::<u8>(3, &[]));
+    }
+
+    #[test]
+    fn test_random() {
+        for seqs in random_sequences(100, 0x2545_f491_4f6c_dd1d, 2..=4, 0..=11, 4) {
+            let seqs = seqs.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+            let Bounds { lower, upper } = bounds(&seqs);
+            assert!(lower <= upper);
+
+            let witness = greedy(&seqs);
+            for s in &seqs {
+                assert!(Automaton::new(s).is_subsequence(&witness));
+            }
+
+            let lcs = reference::lcs_length(&seqs);
+            assert!(lower <= lcs && lcs <= upper, "{:?}", seqs);
+        }
+    }
+}
@@ -9,25 +9,57 @@
 //! automaton with epsilon-transitions.
 
 mod xmcs2;
-pub use xmcs2::xmcs2;
-pub(self) use xmcs2::xmcs2_raw;
+use xmcs2::{check_gap, xmcs2_raw, xmcs2_with_gap_monitored};
+pub use xmcs2::{try_xmcs2, try_xmcs2_with_gap, xmcs2, xmcs2_with_ends, xmcs2_with_gap, Ends};
 
 mod xmcsk;
-pub use xmcsk::xmcsk;
+pub use xmcsk::{
+    try_xmcsk, try_xmcsk_observed, try_xmcsk_with_budget, try_xmcsk_with_gap,
+    try_xmcsk_with_gap_observed, xmcsk, xmcsk_with_gap,
+};
+
+mod budget;
+pub use budget::Budget;
+
+mod constrained;
+
+mod export;
+pub use export::Format;
+
+mod intern;
+
+mod memo;
+
+mod nodes;
+use nodes::Nodes;
+
+mod observer;
+pub use observer::{Control, Observer, Progress};
+
+mod stats;
+pub use stats::Stats;
+
+mod tables;
+
+mod tree;
 
-#[cfg(feature = "graphviz")]
 mod render;
+pub use render::{Formatter, Highlight, RankDir, RenderOptions};
 
 use std::collections::HashSet;
+use std::hash::Hash;
 
 /// Struct used to store a graph representing a set of sequences.
 pub struct Dag<'a, T> {
-    /// Array of nodes
-    nodes: Vec<Node<'a, T>>,
+    /// Array of nodes, the children of a node always have smaller
+    /// indices than the node itself
+    nodes: Nodes<'a, T>,
     /// Index of the first node
     start: usize,
     /// Minimum size of a subsequence
     len: usize,
+    /// Number of nodes shared with an equal node during the construction
+    shared: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -45,8 +77,12 @@ enum NodeType<'a, T> {
     Element { value: T, child: usize },
 }
 
+/// Key used to memoise the computations of the builders: minimum
+/// length of the subsequences, position in the first and in the second
+/// sequence (or graph), and distance from the last matched elements
+/// when computing gap-constrained subsequences (0 otherwise).
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct Position(usize, usize, usize);
+struct Position(usize, usize, usize, usize);
 
 impl<T> Dag<'_, T>
 where
@@ -59,42 +95,126 @@ where
     /// Returns `None` if there is no common subsequence of length more
     /// than `len`.1
     pub fn extract_lcs(&self) -> Option<Vec<T>> {
-        let start = &self.nodes[self.start];
+        let start = self.nodes.get(self.start);
         if start.max_length == 0 {
             return None;
         }
         let mut res = Vec::with_capacity(start.max_length);
-        self.extract_lcs_impl(start, &mut res);
+        let mut current = start;
+
+        loop {
+            match current.inner {
+                NodeType::Empty => break,
+                NodeType::End { suffix } => {
+                    res.extend_from_slice(suffix);
+                    break;
+                }
+                NodeType::Element { value, child } => {
+                    res.push(value);
+                    current = self.nodes.get(child);
+                }
+                NodeType::Split { child1, child2 } => {
+                    let node1 = self.nodes.get(child1);
+                    let node2 = self.nodes.get(child2);
+                    current = if node1.max_length > node2.max_length {
+                        node1
+                    } else {
+                        node2
+                    };
+                }
+            }
+        }
+
         Some(res)
     }
 
-    fn extract_lcs_impl(&self, current: &Node<T>, buffer: &mut Vec<T>) {
-        match current.inner {
-            NodeType::Empty => (),
-            NodeType::End { suffix } => buffer.extend_from_slice(suffix),
-            NodeType::Element { value, child } => {
-                buffer.push(value);
-                self.extract_lcs_impl(&self.nodes[child], buffer);
-            }
-            NodeType::Split { child1, child2 } => {
-                let node1 = &self.nodes[child1];
-                let node2 = &self.nodes[child2];
-                if node1.max_length > node2.max_length {
-                    self.extract_lcs_impl(node1, buffer)
-                } else {
-                    self.extract_lcs_impl(node2, buffer)
+    /// Number of paths from the first node to an End node, that is the
+    /// number of sequences represented by this graph, a sequence spelled
+    /// by several paths being counted once for each
+    ///
+    /// Runs in time linear in the number of nodes, unlike counting the
+    /// sequences of [`to_set`](Dag::to_set). Saturates at `usize::MAX`.
+    ///
+    /// # Examples
+    /// ```
+    /// let dag = xmcs::dag::xmcs2(3, b"ABCD", b"ACBD");
+    /// assert_eq!(2, dag.path_count());
+    /// ```
+    pub fn path_count(&self) -> usize {
+        // Children have smaller indices than their parents, so they are
+        // counted first
+        let mut counts = Vec::with_capacity(self.start + 1);
+        for node in self.nodes.iter().take(self.start + 1) {
+            let count = match node.inner {
+                NodeType::Empty => 0,
+                NodeType::End { .. } => 1,
+                NodeType::Element { child, .. } => counts[child],
+                NodeType::Split { child1, child2 } => {
+                    usize::saturating_add(counts[child1], counts[child2])
                 }
-            }
+            };
+            counts.push(count);
         }
+        counts[self.start]
     }
 
-    pub fn to_set(&self) -> HashSet<T> {
-        todo!();
+    /// Calls `f` on each sequence represented by this graph, once for
+    /// each path spelling it, stopping at the first error
+    ///
+    /// The sequences are built one at a time in a buffer, so unlike
+    /// [`to_set`](Dag::to_set) this only uses memory proportional to the
+    /// length of the longest sequence.
+    ///
+    /// # Errors
+    /// Forwards the first error returned by `f`.
+    ///
+    /// # Examples
+    /// ```
+    /// let dag = xmcs::dag::xmcs2(3, b"ABCD", b"ACBD");
+    ///
+    /// let mut seqs = Vec::new();
+    /// dag.for_each_sequence(|s| -> Result<(), ()> {
+    ///     seqs.push(s.to_vec());
+    ///     Ok(())
+    /// })
+    /// .unwrap();
+    /// seqs.sort();
+    /// assert_eq!(vec![b"ABD".to_vec(), b"ACD".to_vec()], seqs);
+    /// ```
+    pub fn for_each_sequence<E>(
+        &self,
+        mut f: impl FnMut(&[T]) -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut buffer = Vec::new();
+        // Nodes left to visit, with the length of the prefix leading to
+        // them
+        let mut stack = vec![(self.start, 0)];
+
+        while let Some((current, depth)) = stack.pop() {
+            buffer.truncate(depth);
+            match self.nodes.get(current).inner {
+                NodeType::Empty => (),
+                NodeType::End { suffix } => {
+                    buffer.extend_from_slice(suffix);
+                    f(&buffer)?;
+                }
+                NodeType::Element { value, child } => {
+                    buffer.push(value);
+                    stack.push((child, depth + 1));
+                }
+                NodeType::Split { child1, child2 } => {
+                    stack.push((child2, depth));
+                    stack.push((child1, depth));
+                }
+            }
+        }
+
+        Ok(())
     }
 
     /// Construct a graph representing the empty set
     pub fn empty(len: usize) -> Self {
-        let mut nodes = Vec::new();
+        let mut nodes = Nodes::default();
         nodes.push(Node {
             max_length: 0,
             min_length: 0,
@@ -105,15 +225,173 @@ where
             nodes,
             start: 0,
             len,
+            shared: 0,
         }
     }
 }
 
+impl<T> Dag<'_, T>
+where
+    T: Eq + Hash + Copy,
+{
+    /// Enumerate all the sequences represented by this graph
+    ///
+    /// The number of sequences can be exponential in the size of
+    /// the graph, this should only be used on small graphs.
+    pub fn to_set(&self) -> HashSet<Vec<T>> {
+        let mut res = HashSet::new();
+        let mut buffer = Vec::new();
+        // Nodes left to visit, with the length of the prefix leading to
+        // them
+        let mut stack = vec![(self.start, 0)];
+
+        while let Some((current, depth)) = stack.pop() {
+            buffer.truncate(depth);
+            match self.nodes.get(current).inner {
+                NodeType::Empty => (),
+                NodeType::End { suffix } => {
+                    let mut seq = Vec::with_capacity(buffer.len() + suffix.len());
+                    seq.extend_from_slice(&buffer);
+                    seq.extend_from_slice(suffix);
+                    res.insert(seq);
+                }
+                NodeType::Element { value, child } => {
+                    buffer.push(value);
+                    stack.push((child, depth + 1));
+                }
+                NodeType::Split { child1, child2 } => {
+                    stack.push((child2, depth));
+                    stack.push((child1, depth));
+                }
+            }
+        }
+
+        res
+    }
+}
+
+impl<T> Dag<'_, T>
+where
+    T: Eq + Copy,
+{
+    /// Find how `seq` is represented in this graph
+    ///
+    /// Returns the indices of the nodes of a path from the first node
+    /// spelling `seq`, ending with the End node containing its last
+    /// elements, or `None` if the graph does not contain `seq`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use xmcs::dag::xmcs2;
+    /// let dag = xmcs2(3, b"ABCD", b"ACBD");
+    ///
+    /// let lcs = dag.extract_lcs().unwrap();
+    /// assert!(dag.path_of(&lcs).is_some());
+    /// assert!(dag.path_of(b"ABD").is_some());
+    /// assert_eq!(None, dag.path_of(b"ABC"));
+    /// ```
+    pub fn path_of(&self, seq: &[T]) -> Option<Vec<usize>> {
+        let mut path = Vec::new();
+        let mut visited = HashSet::new();
+        // Nodes left to visit, with the number of elements of `seq`
+        // before them and the length of the path leading to them
+        let mut stack = vec![(self.start, 0, 0)];
+
+        while let Some((current, matched, depth)) = stack.pop() {
+            // A position seen before did not lead to `seq`
+            if !visited.insert((current, matched)) {
+                continue;
+            }
+            path.truncate(depth);
+            path.push(current);
+
+            match self.nodes.get(current).inner {
+                NodeType::Empty => (),
+                NodeType::End { suffix } => {
+                    if suffix == &seq[matched..] {
+                        return Some(path);
+                    }
+                }
+                NodeType::Element { value, child } => {
+                    if seq.get(matched) == Some(&value) {
+                        stack.push((child, matched + 1, depth + 1));
+                    }
+                }
+                NodeType::Split { child1, child2 } => {
+                    stack.push((child2, matched, depth + 1));
+                    stack.push((child1, matched, depth + 1));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl<T> Dag<'_, T> {
+    /// Number of nodes used to represent the graph
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Number of nodes that were not inserted during the construction of
+    /// the graph because a node with the same content already existed
+    ///
+    /// This includes the nodes shared while building the intermediate
+    /// graphs of [`xmcsk`].
+    pub fn shared_count(&self) -> usize {
+        self.shared
+    }
+}
+
 impl<'a, T> Dag<'a, T> {
+    /// Construct a graph from the nodes computed by a builder and the
+    /// index of its first node, `None` meaning the graph is empty, and
+    /// the number of nodes it shared.
+    fn from_raw(
+        mut nodes: Nodes<'a, T>,
+        start: Option<usize>,
+        len: usize,
+        shared: usize,
+    ) -> Self
+    where
+        T: Copy,
+    {
+        // Set of subsequences is empty
+        if start.is_none() {
+            // If there is no subsequence, the graph should be empty
+            assert!(nodes.is_empty());
+
+            nodes.push(Node {
+                max_length: 0,
+                min_length: 0,
+                inner: NodeType::Empty,
+            });
+        }
+
+        // The builders push the children of a node before it, which
+        // `levels` and `path_count` rely on
+        debug_assert!(nodes
+            .iter()
+            .enumerate()
+            .all(|(idx, node)| match node.inner {
+                NodeType::Split { child1, child2 } => child1 < idx && child2 < idx,
+                NodeType::Element { child, .. } => child < idx,
+                NodeType::Empty | NodeType::End { .. } => true,
+            }));
+
+        Self {
+            nodes,
+            start: start.unwrap_or(0),
+            len,
+            shared,
+        }
+    }
+
     /// Construct a graph representing a singleton containing
     /// one string
-    pub fn singleton(len: usize, seq: &'a [T]) -> Dag<'a, T> {
-        let mut nodes = Vec::new();
+    pub fn singleton(len: usize, seq: &'a [T]) -> Self {
+        let mut nodes = Nodes::default();
         nodes.push(Node {
             max_length: seq.len(),
             min_length: seq.len(),
@@ -124,24 +402,25 @@ impl<'a, T> Dag<'a, T> {
             nodes,
             start: 0,
             len,
+            shared: 0,
         }
     }
 }
 
-impl<'a, T> Node<'a, T> {
+impl<T> Node<'_, T> {
     /// Change the index of the children of this node
-    /// so that they are still valid if all nodes
-    /// are shifted by `index` in the array of nodes.
+    /// with `f`, so that they are still valid once the nodes
+    /// are moved to another array of nodes.
     /// This is useful to insert a subgraph into another graph.
-    fn with_base_index(self, index: usize) -> Node<'a, T> {
+    fn with_indices(self, f: impl Fn(usize) -> usize) -> Self {
         let node_type = match self.inner {
             NodeType::Element { value, child } => NodeType::Element {
-                value: value,
-                child: child + index,
+                value,
+                child: f(child),
             },
             NodeType::Split { child1, child2 } => NodeType::Split {
-                child1: child1 + index,
-                child2: child2 + index,
+                child1: f(child1),
+                child2: f(child2),
             },
             node_type => node_type,
         };
@@ -154,9 +433,51 @@ impl<'a, T> Node<'a, T> {
     }
 
     fn is_split_with_child(&self, index: usize) -> bool {
-        match self.inner {
-            NodeType::Split { child1, child2 } if child1 == index || child2 == index => true,
-            _ => false,
+        matches!(self.inner, NodeType::Split { child1, child2 } if child1 == index || child2 == index)
+    }
+}
+
+// === Tests ===
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::reference::random_sequences;
+
+    #[test]
+    fn test_path_count() {
+        assert_eq!(0, Dag::<u8>::empty(3).path_count());
+        assert_eq!(1, Dag::singleton(3, b"ABC").path_count());
+
+        for seqs in random_sequences(100, 0x2545_f491_4f6c_dd1d, 2..=3, 0..=8, 3) {
+            let seqs = seqs.iter().map(Vec::as_slice).collect::<Vec<_>>();
+            let dag = xmcsk(1, &seqs);
+
+            let mut paths = Vec::new();
+            dag.for_each_sequence(|s| -> Result<(), ()> {
+                paths.push(s.to_vec());
+                Ok(())
+            })
+            .unwrap();
+
+            assert_eq!(paths.len(), dag.path_count(), "{seqs:?}");
+            assert_eq!(dag.to_set(), paths.into_iter().collect());
         }
     }
+
+    #[test]
+    fn test_for_each_sequence_error() {
+        let dag = xmcs2(1, b"ABCD", b"ACBD");
+        assert!(dag.path_count() > 1);
+
+        // Stops at the first error
+        let mut calls = 0;
+        let res = dag.for_each_sequence(|s| {
+            calls += 1;
+            Err(s.len())
+        });
+        assert_eq!(1, calls);
+        assert!(res.is_err());
+    }
 }
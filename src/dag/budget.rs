@@ -0,0 +1,130 @@
+//! Limits on the resources used by the builders
+//!
+//! When `len` is much lower than the length of a longest common
+//! subsequence, the graphs and the memoisation tables can grow until the
+//! memory is exhausted. The builders check a [`Budget`] while they run
+//! and stop with [`Error::BudgetExceeded`] as soon as it is exceeded.
+
+#[cfg(feature = "compact")]
+use std::cmp::min;
+use std::ops::Add;
+
+#[cfg(feature = "compact")]
+use super::nodes::MAX_NODES;
+use crate::Error;
+
+/// Limits on the resources used to build a graph
+///
+/// All the limits are disabled by default. They apply to the graph being
+/// built when adding a sequence, while the graph of the previous
+/// sequences is also kept in memory and counts towards `max_bytes`.
+///
+/// With the `compact` feature, `max_nodes` is always at most `2^31`, as
+/// the nodes are stored with 32-bit indices.
+///
+/// # Examples
+/// ```
+/// # use xmcs::dag::{try_xmcsk_with_budget, Budget};
+/// # use xmcs::Error;
+/// let seqs = [&b"ADBCBAD"[..], b"ADCBACD", b"ABDCABDA"];
+///
+/// let budget = Budget {
+///     max_nodes: Some(2),
+///     ..Budget::default()
+/// };
+/// let res = try_xmcsk_with_budget(1, &seqs, &budget);
+/// assert!(matches!(res, Err(Error::BudgetExceeded { .. })));
+///
+/// let budget = Budget {
+///     max_bytes: Some(1 << 20),
+///     ..Budget::default()
+/// };
+/// let dag = try_xmcsk_with_budget(1, &seqs, &budget).unwrap();
+/// assert_eq!(Some(5), dag.extract_lcs().map(|s| s.len()));
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    /// Maximum number of nodes of the graph
+    pub max_nodes: Option<usize>,
+    /// Maximum number of positions stored in the memoisation table
+    pub max_memo_entries: Option<usize>,
+    /// Maximum number of bytes used by the graphs and the tables, as
+    /// estimated from the size of their allocations
+    pub max_bytes: Option<usize>,
+}
+
+/// Resources used by a builder
+#[derive(Debug, Default, Clone, Copy)]
+pub(super) struct Usage {
+    /// Number of nodes of the graph
+    pub(super) nodes: usize,
+    /// Number of positions stored in the memoisation table
+    pub(super) memo_entries: usize,
+    /// Estimated number of bytes allocated
+    pub(super) bytes: usize,
+}
+
+impl Budget {
+    /// Budget with `max_nodes` limited to the number of nodes that can be
+    /// stored
+    #[cfg(not(feature = "compact"))]
+    pub(super) const fn capped(self) -> Self {
+        self
+    }
+
+    /// Budget with `max_nodes` limited to the number of nodes that can be
+    /// stored
+    #[cfg(feature = "compact")]
+    pub(super) fn capped(self) -> Self {
+        let max_nodes = self.max_nodes.map_or(MAX_NODES, |max| min(max, MAX_NODES));
+        Self {
+            max_nodes: Some(max_nodes),
+            ..self
+        }
+    }
+
+    /// Returns whether a table of `bytes` bytes fits in the budget
+    pub(super) fn allows_bytes(&self, bytes: usize) -> bool {
+        self.max_bytes.is_none_or(|max| bytes <= max)
+    }
+
+    /// Returns an error if `usage` exceeds one of the limits, while
+    /// adding the sequence at index `sequence`
+    pub(super) fn check(&self, sequence: usize, usage: Usage) -> Result<(), Error> {
+        let exceeds = |limit: Option<usize>, value| limit.is_some_and(|limit| value > limit);
+
+        if exceeds(self.max_nodes, usage.nodes)
+            || exceeds(self.max_memo_entries, usage.memo_entries)
+            || exceeds(self.max_bytes, usage.bytes)
+        {
+            Err(usage.exceeded(sequence))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Usage {
+    /// Error reporting these resources as exceeding the budget, while
+    /// adding the sequence at index `sequence`
+    pub(super) const fn exceeded(self, sequence: usize) -> Error {
+        Error::BudgetExceeded {
+            sequence,
+            nodes: self.nodes,
+            memo_entries: self.memo_entries,
+            bytes: self.bytes,
+        }
+    }
+}
+
+impl Add for Usage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            nodes: self.nodes + other.nodes,
+            memo_entries: self.memo_entries + other.memo_entries,
+            bytes: self.bytes + other.bytes,
+        }
+    }
+}
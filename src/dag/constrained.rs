@@ -0,0 +1,310 @@
+//! Restrict a graph to the sequences containing a given pattern
+//!
+//! This is used to solve the constrained longest common subsequence
+//! problem: find a longest common subsequence of several sequences that
+//! contains a given pattern as a subsequence.
+//!
+//! Any common subsequence containing the pattern is itself a subsequence
+//! of a maximal common subsequence, which also contains the pattern.
+//! Thus, if a constrained longest common subsequence has length at least
+//! `len`, a longest sequence of the filtered extended set of maximal
+//! common subsequences is a constrained longest common subsequence.
+
+use super::intern::Interner;
+use super::{Dag, Node, NodeType, Nodes};
+
+use std::cmp::{max, min};
+use std::collections::HashMap;
+
+/// Intermediate structure used to compute the product of a graph
+/// with a pattern
+struct Filter<'a, 'g, T> {
+    /// Graph being filtered
+    graph: &'g Dag<'a, T>,
+    /// Pattern that must be contained in all the sequences
+    pattern: &'g [T],
+    /// Array of nodes of the new graph
+    nodes: Nodes<'a, T>,
+    /// Index in the new graph of a node of the filtered graph, given
+    /// the number of elements of the pattern already matched
+    memo: HashMap<(usize, usize), Option<usize>>,
+    /// Used to share the nodes with the same content
+    interner: Interner,
+}
+
+/// Progress of a call of [`Filter::compute`] in its explicit call stack,
+/// giving the meaning of the value returned by the last finished call
+#[derive(Clone, Copy)]
+enum Step {
+    /// Not started yet
+    Enter,
+    /// Subgraph after the element of an element node
+    Element,
+    /// First subgraph of a split node
+    First,
+    /// Second subgraph of a split node, along with the first one
+    Second(Option<usize>),
+}
+
+impl<'a, 'g, T> Filter<'a, 'g, T>
+where
+    T: Eq + Copy,
+{
+    fn build(graph: &'g Dag<'a, T>, pattern: &'g [T]) -> Dag<'a, T> {
+        let mut res = Self {
+            graph,
+            pattern,
+            nodes: Nodes::default(),
+            memo: HashMap::new(),
+            interner: Interner::default(),
+        };
+
+        let start = res.compute(graph.start, 0);
+        Dag::from_raw(res.nodes, start, graph.len, res.interner.shared())
+    }
+
+    /// Compute the subgraph representing the sequences of the subgraph
+    /// starting at `current` that contain `pattern[matched..]`
+    ///
+    /// Uses an explicit stack of calls so that long sequences do not
+    /// overflow the thread stack.
+    fn compute(&mut self, current: usize, matched: usize) -> Option<usize> {
+        let graph = self.graph;
+        let mut stack = vec![(current, matched, Step::Enter)];
+        // Value returned by the last finished call
+        let mut ret = None;
+
+        while let Some((current, matched, step)) = stack.pop() {
+            let res = match (step, graph.nodes.get(current).inner) {
+                (Step::Enter, inner) => {
+                    if let Some(&index) = self.memo.get(&(current, matched)) {
+                        ret = index;
+                        continue;
+                    }
+
+                    match inner {
+                        NodeType::Empty => None,
+
+                        NodeType::End { suffix } => {
+                            if is_subsequence(&self.pattern[matched..], suffix) {
+                                Some(self.push(NodeType::End { suffix }))
+                            } else {
+                                None
+                            }
+                        }
+
+                        NodeType::Element { value, child } => {
+                            // Matching the first possible element of the
+                            // pattern is always the best choice
+                            let next = match self.pattern.get(matched) {
+                                Some(&e) if e == value => matched + 1,
+                                _ => matched,
+                            };
+
+                            stack.push((current, matched, Step::Element));
+                            stack.push((child, next, Step::Enter));
+                            continue;
+                        }
+
+                        NodeType::Split { child1, .. } => {
+                            stack.push((current, matched, Step::First));
+                            stack.push((child1, matched, Step::Enter));
+                            continue;
+                        }
+                    }
+                }
+
+                (Step::Element, NodeType::Element { value, .. }) => {
+                    ret.map(|child| self.push(NodeType::Element { value, child }))
+                }
+
+                (Step::First, NodeType::Split { child2, .. }) => {
+                    stack.push((current, matched, Step::Second(ret)));
+                    stack.push((child2, matched, Step::Enter));
+                    continue;
+                }
+
+                (Step::Second(index1), _) => match (index1, ret) {
+                    (None, None) => None,
+                    (Some(idx), None) | (None, Some(idx)) => Some(idx),
+                    (Some(idx1), Some(idx2)) if idx1 == idx2 => Some(idx1),
+                    (Some(child1), Some(child2)) => {
+                        Some(self.push(NodeType::Split { child1, child2 }))
+                    }
+                },
+
+                _ => unreachable!(),
+            };
+
+            self.memo.insert((current, matched), res);
+            ret = res;
+        }
+
+        ret
+    }
+
+    /// Insert a new node, computing its lengths from its children, or
+    /// find an existing node with the same content
+    fn push(&mut self, inner: NodeType<'a, T>) -> usize {
+        let (max_length, min_length) = match inner {
+            NodeType::Empty => (0, 0),
+            NodeType::End { suffix } => (suffix.len(), suffix.len()),
+            NodeType::Element { child, .. } => {
+                let node = self.nodes.get(child);
+                (node.max_length + 1, node.min_length + 1)
+            }
+            NodeType::Split { child1, child2 } => {
+                let node1 = self.nodes.get(child1);
+                let node2 = self.nodes.get(child2);
+                (
+                    max(node1.max_length, node2.max_length),
+                    min(node1.min_length, node2.min_length),
+                )
+            }
+        };
+
+        let node = Node {
+            max_length,
+            min_length,
+            inner,
+        };
+        self.interner
+            .insert(&mut self.nodes, node)
+            .expect("graph too large for the `compact` feature")
+    }
+}
+
+/// Returns whether `pattern` is a subsequence of `seq`
+fn is_subsequence<T: Eq>(pattern: &[T], seq: &[T]) -> bool {
+    let mut seq = seq.iter();
+    pattern.iter().all(|e| seq.any(|x| x == e))
+}
+
+impl<T> Dag<'_, T>
+where
+    T: Eq + Copy,
+{
+    /// Restrict the graph to the sequences containing `pattern`
+    ///
+    /// Returns a graph representing only the sequences of `self` that
+    /// contain `pattern` as a subsequence.
+    ///
+    /// # Examples
+    /// ```
+    /// let dag = xmcs::dag::xmcs2(3, b"ABCD", b"ACBD");
+    ///
+    /// let constrained = dag.constrain(b"C");
+    /// assert_eq!(Some(b"ACD".to_vec()), constrained.extract_lcs());
+    /// ```
+    ///
+    /// # Panics
+    /// With the `compact` feature, panics if the new graph has more than
+    /// `2^31` nodes.
+    #[must_use]
+    pub fn constrain(&self, pattern: &[T]) -> Self {
+        Filter::build(self, pattern)
+    }
+
+    /// Extract one of the longest subsequence containing `pattern`
+    ///
+    /// If `self` represents an extended set of maximal common
+    /// subsequences of some sequences, returns a longest common
+    /// subsequence of these sequences that contains `pattern` as a
+    /// subsequence.
+    /// Returns `None` if there is no such subsequence of length at least
+    /// `len`.
+    ///
+    /// # Panics
+    /// In the same cases as [`constrain`](Dag::constrain).
+    pub fn extract_constrained_lcs(&self, pattern: &[T]) -> Option<Vec<T>> {
+        self.constrain(pattern)
+            .extract_lcs()
+            .filter(|lcs| lcs.len() >= self.len)
+    }
+}
+
+// === Tests ===
+
+#[cfg(test)]
+mod test {
+    use super::super::{xmcs2, xmcsk, Dag};
+    use super::is_subsequence;
+
+    use std::cmp::min;
+
+    use crate::reference::random_sequences;
+
+    /// Length of a longest common subsequence of `seqs` containing
+    /// `pattern`, by enumerating the subsequences of the first sequence
+    fn brute_force(seqs: &[&[u8]], pattern: &[u8]) -> Option<usize> {
+        let first = seqs[0];
+        (0..1_u32 << first.len())
+            .map(|mask| {
+                (0..first.len())
+                    .filter(|i| mask >> i & 1 == 1)
+                    .map(|i| first[i])
+                    .collect::<Vec<_>>()
+            })
+            .filter(|sub| is_subsequence(pattern, sub))
+            .filter(|sub| seqs.iter().all(|s| is_subsequence(sub, s)))
+            .map(|sub| sub.len())
+            .max()
+    }
+
+    #[test]
+    fn test_constrain() {
+        let dag = xmcs2(3, b"ADBCBAD", b"ADCBACD");
+
+        assert_eq!(Some(6), dag.extract_lcs().map(|s| s.len()));
+
+        let lcs = dag.extract_constrained_lcs(b"BC").unwrap();
+        assert_eq!(b"ADBCD", &lcs[..]);
+
+        let set = dag.constrain(b"CD").to_set();
+        assert!(!set.is_empty());
+        for s in &set {
+            assert!(is_subsequence(b"CD", s));
+        }
+
+        assert_eq!(None, dag.extract_constrained_lcs(b"DDD"));
+
+        // The only sequence containing `BC` is shorter than `len`
+        let dag = Dag::singleton(5, b"ABCD");
+        assert_eq!(None, dag.extract_constrained_lcs(b"BC"));
+    }
+
+    #[test]
+    fn test_random() {
+        for seqs in random_sequences(100, 0x2545_f491_4f6c_dd1d, 2..=3, 1..=8, 3) {
+            let seqs = seqs.iter().map(Vec::as_slice).collect::<Vec<_>>();
+            let shortest = seqs.iter().map(|s| s.len()).min().unwrap_or(0);
+
+            // The empty pattern, a pattern absent from every input, and
+            // parts of the second input, which may be absent from the
+            // first one or longer than `len`
+            let second = seqs[1];
+            let mut patterns = vec![&b""[..], b"D"];
+            patterns.extend((0..second.len()).map(|i| &second[i..min(i + 3, second.len())]));
+
+            for len in 1..=shortest {
+                let dag = xmcsk(len, &seqs);
+
+                for &pattern in &patterns {
+                    let expected = brute_force(&seqs, pattern).filter(|&l| l >= len);
+                    let lcs = dag.extract_constrained_lcs(pattern);
+                    assert_eq!(expected, lcs.as_ref().map(Vec::len), "{seqs:?} {pattern:?}");
+
+                    if let Some(lcs) = lcs {
+                        assert!(is_subsequence(pattern, &lcs));
+                        assert!(seqs.iter().all(|s| is_subsequence(&lcs, s)));
+                    }
+                }
+
+                // Without a pattern, same as the unconstrained problem
+                let lcs = dag.extract_lcs().filter(|lcs| lcs.len() >= len);
+                let constrained = dag.extract_constrained_lcs(b"");
+                assert_eq!(lcs.map(|s| s.len()), constrained.map(|s| s.len()));
+            }
+        }
+    }
+}
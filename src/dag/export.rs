@@ -0,0 +1,471 @@
+//! Export of the graphs to the formats read by other tools
+//!
+//! The nodes reachable from the first node are visited once, and given
+//! to a backend writing them in one of the [`Format`]s. As in the
+//! output of [`format_graph`](Dag::format_graph), the element of an
+//! Element node labels the edge to its child, and the End nodes are
+//! labelled with their suffix.
+//!
+//! The DOT output is the one of `format_graph`, written by the `render`
+//! module, and deliberately does not go through a backend: it groups the
+//! nodes by level and gives each level a rank, which needs the levels of
+//! the whole graph before the first node is written, while a backend
+//! receives the nodes one by one in depth-first order.
+
+use super::render::RenderOptions;
+use super::{Dag, NodeType};
+
+use std::fmt::{Display, Write as _};
+use std::io::{Result, Write};
+
+/// Format of an exported graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// JSON object with the lists of nodes and edges
+    Json,
+    /// [GraphML](http://graphml.graphdrawing.org/) document
+    GraphMl,
+    /// [Mermaid](https://mermaid.js.org/) flowchart
+    Mermaid,
+    /// Code for the [dot](https://graphviz.org/) tool, as written by
+    /// [`format_graph`](Dag::format_graph)
+    Dot,
+}
+
+/// Node of an exported graph
+struct Vertex {
+    id: usize,
+    /// Type of the node: `empty`, `end`, `split` or `element`
+    kind: &'static str,
+    /// Suffix of an End node, or element of an Element node
+    label: Option<String>,
+    max_length: usize,
+    min_length: usize,
+}
+
+/// Edge of an exported graph, labelled with the element of an Element
+/// node
+struct Edge {
+    from: usize,
+    to: usize,
+    label: Option<String>,
+}
+
+/// Writes the graph in one of the formats, the nodes being given before
+/// the edges
+trait Backend {
+    fn begin(&mut self, w: &mut dyn Write, len: usize, start: usize) -> Result<()>;
+    fn vertex(&mut self, w: &mut dyn Write, vertex: &Vertex) -> Result<()>;
+    fn edge(&mut self, w: &mut dyn Write, edge: &Edge) -> Result<()>;
+    fn end(&mut self, w: &mut dyn Write) -> Result<()>;
+}
+
+impl<T> Dag<'_, T>
+where
+    T: Display + Copy,
+{
+    /// Writes the graph into `w` in the given `format`
+    ///
+    /// # Errors
+    /// Forwards errors from writing into `w`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use xmcs::dag::{Dag, Format};
+    /// let seq = ['A', 'B'];
+    /// let dag = Dag::singleton(2, &seq);
+    ///
+    /// let mut mermaid = Vec::new();
+    /// dag.export(Format::Mermaid, &mut mermaid).unwrap();
+    /// assert!(String::from_utf8(mermaid).unwrap().contains(r#"n0["AB"]"#));
+    /// ```
+    pub fn export(&self, format: Format, w: &mut impl Write) -> Result<()> {
+        self.export_with(format, w, ToString::to_string)
+    }
+}
+
+impl<T> Dag<'_, T>
+where
+    T: Copy,
+{
+    /// Same as [`export`](Dag::export), using `label` to format the
+    /// elements of the sequences.
+    pub(crate) fn export_with(
+        &self,
+        format: Format,
+        w: &mut impl Write,
+        label: impl Fn(&T) -> String,
+    ) -> Result<()> {
+        let mut backend: Box<dyn Backend> = match format {
+            Format::Json => Box::new(Json::default()),
+            Format::GraphMl => Box::new(GraphMl),
+            Format::Mermaid => Box::new(Mermaid),
+            Format::Dot => return self.render(w, &label, &RenderOptions::default()),
+        };
+        self.traverse(&mut *backend, w, &label)
+    }
+
+    /// Gives the nodes reachable from the first node to `backend`, in
+    /// depth-first order, then their edges
+    fn traverse(
+        &self,
+        backend: &mut dyn Backend,
+        w: &mut dyn Write,
+        label: &dyn Fn(&T) -> String,
+    ) -> Result<()> {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack = vec![self.start];
+        let mut edges = Vec::new();
+
+        backend.begin(w, self.len, self.start)?;
+        while let Some(id) = stack.pop() {
+            if visited[id] {
+                continue;
+            }
+            visited[id] = true;
+
+            let node = self.nodes.get(id);
+            let (kind, text) = match node.inner {
+                NodeType::Empty => ("empty", None),
+                NodeType::End { suffix } => ("end", Some(suffix.iter().map(label).collect())),
+                NodeType::Split { child1, child2 } => {
+                    edges.push(Edge {
+                        from: id,
+                        to: child1,
+                        label: None,
+                    });
+                    edges.push(Edge {
+                        from: id,
+                        to: child2,
+                        label: None,
+                    });
+                    stack.push(child2);
+                    stack.push(child1);
+                    ("split", None)
+                }
+                NodeType::Element { value, child } => {
+                    edges.push(Edge {
+                        from: id,
+                        to: child,
+                        label: Some(label(&value)),
+                    });
+                    stack.push(child);
+                    ("element", Some(label(&value)))
+                }
+            };
+
+            backend.vertex(
+                w,
+                &Vertex {
+                    id,
+                    kind,
+                    label: text,
+                    max_length: node.max_length,
+                    min_length: node.min_length,
+                },
+            )?;
+        }
+
+        for edge in &edges {
+            backend.edge(w, edge)?;
+        }
+        backend.end(w)
+    }
+}
+
+/// JSON object: `{"len": ..., "start": ..., "nodes": [...], "edges": [...]}`
+#[derive(Default)]
+struct Json {
+    /// Whether an edge was written, the nodes are closed before the
+    /// first one
+    edges: bool,
+    /// Whether the current list already has an item
+    items: bool,
+}
+
+impl Json {
+    /// Separator before the next item of the current list
+    fn separator(&mut self) -> &'static str {
+        if std::mem::replace(&mut self.items, true) {
+            ",\n    "
+        } else {
+            "\n    "
+        }
+    }
+}
+
+impl Backend for Json {
+    fn begin(&mut self, w: &mut dyn Write, len: usize, start: usize) -> Result<()> {
+        write!(
+            w,
+            "{{\n  \"len\": {len},\n  \"start\": {start},\n  \"nodes\": ["
+        )
+    }
+
+    fn vertex(&mut self, w: &mut dyn Write, vertex: &Vertex) -> Result<()> {
+        write!(
+            w,
+            r#"{}{{"id": {}, "type": "{}", "max_length": {}, "min_length": {}"#,
+            self.separator(),
+            vertex.id,
+            vertex.kind,
+            vertex.max_length,
+            vertex.min_length
+        )?;
+        if let Some(label) = &vertex.label {
+            write!(w, r#", "label": "{}""#, escape_json(label))?;
+        }
+        write!(w, "}}")
+    }
+
+    fn edge(&mut self, w: &mut dyn Write, edge: &Edge) -> Result<()> {
+        if !std::mem::replace(&mut self.edges, true) {
+            write!(w, "\n  ],\n  \"edges\": [")?;
+            self.items = false;
+        }
+        write!(
+            w,
+            r#"{}{{"source": {}, "target": {}"#,
+            self.separator(),
+            edge.from,
+            edge.to
+        )?;
+        if let Some(label) = &edge.label {
+            write!(w, r#", "label": "{}""#, escape_json(label))?;
+        }
+        write!(w, "}}")
+    }
+
+    fn end(&mut self, w: &mut dyn Write) -> Result<()> {
+        if !self.edges {
+            write!(w, "\n  ],\n  \"edges\": [")?;
+        }
+        writeln!(w, "\n  ]\n}}")
+    }
+}
+
+/// Document in the [`Format::GraphMl`] format, the nodes having the
+/// attributes `type`, `label`, `max_length` and `min_length`, and the
+/// edges a `label`
+struct GraphMl;
+
+impl Backend for GraphMl {
+    fn begin(&mut self, w: &mut dyn Write, len: usize, start: usize) -> Result<()> {
+        writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            w,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )?;
+        for (id, domain, name, kind) in &[
+            ("type", "node", "type", "string"),
+            ("label", "node", "label", "string"),
+            ("max_length", "node", "max_length", "int"),
+            ("min_length", "node", "min_length", "int"),
+            ("edge_label", "edge", "label", "string"),
+            ("len", "graph", "len", "int"),
+            ("start", "graph", "start", "string"),
+        ] {
+            writeln!(
+                w,
+                r#"  <key id="{id}" for="{domain}" attr.name="{name}" attr.type="{kind}"/>"#
+            )?;
+        }
+        writeln!(w, r#"  <graph id="xmcs" edgedefault="directed">"#)?;
+        writeln!(w, r#"    <data key="len">{len}</data>"#)?;
+        writeln!(w, r#"    <data key="start">n{start}</data>"#)
+    }
+
+    fn vertex(&mut self, w: &mut dyn Write, vertex: &Vertex) -> Result<()> {
+        writeln!(w, r#"    <node id="n{}">"#, vertex.id)?;
+        writeln!(w, r#"      <data key="type">{}</data>"#, vertex.kind)?;
+        if let Some(label) = &vertex.label {
+            writeln!(w, r#"      <data key="label">{}</data>"#, escape_xml(label))?;
+        }
+        writeln!(
+            w,
+            r#"      <data key="max_length">{}</data>"#,
+            vertex.max_length
+        )?;
+        writeln!(
+            w,
+            r#"      <data key="min_length">{}</data>"#,
+            vertex.min_length
+        )?;
+        writeln!(w, "    </node>")
+    }
+
+    fn edge(&mut self, w: &mut dyn Write, edge: &Edge) -> Result<()> {
+        match &edge.label {
+            Some(label) => writeln!(
+                w,
+                r#"    <edge source="n{}" target="n{}"><data key="edge_label">{}</data></edge>"#,
+                edge.from,
+                edge.to,
+                escape_xml(label)
+            ),
+            None => writeln!(
+                w,
+                r#"    <edge source="n{}" target="n{}"/>"#,
+                edge.from, edge.to
+            ),
+        }
+    }
+
+    fn end(&mut self, w: &mut dyn Write) -> Result<()> {
+        writeln!(w, "  </graph>")?;
+        writeln!(w, "</graphml>")
+    }
+}
+
+/// Mermaid flowchart, the End nodes being boxes with their suffix and
+/// the other nodes small circles
+struct Mermaid;
+
+impl Backend for Mermaid {
+    fn begin(&mut self, w: &mut dyn Write, _: usize, start: usize) -> Result<()> {
+        writeln!(w, "flowchart LR")?;
+        writeln!(w, "    start([start]) --> n{start}")
+    }
+
+    fn vertex(&mut self, w: &mut dyn Write, vertex: &Vertex) -> Result<()> {
+        match (vertex.kind, &vertex.label) {
+            ("end", Some(suffix)) => {
+                writeln!(w, r#"    n{}["{}"]"#, vertex.id, escape_mermaid(suffix))
+            }
+            ("empty", _) => writeln!(w, r#"    n{}["∅"]"#, vertex.id),
+            _ => writeln!(w, r#"    n{}(("{}"))"#, vertex.id, vertex.id),
+        }
+    }
+
+    fn edge(&mut self, w: &mut dyn Write, edge: &Edge) -> Result<()> {
+        match &edge.label {
+            Some(label) => writeln!(
+                w,
+                r#"    n{} -->|"{}"| n{}"#,
+                edge.from,
+                escape_mermaid(label),
+                edge.to
+            ),
+            None => writeln!(w, "    n{} --> n{}", edge.from, edge.to),
+        }
+    }
+
+    fn end(&mut self, _: &mut dyn Write) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Escapes `s` to be written between the quotes of a JSON string
+fn escape_json(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(res, "\\u{:04x}", u32::from(c));
+            }
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Escapes `s` to be written in the content or the attributes of an
+/// XML element
+fn escape_xml(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+/// Escapes `s` to be written between the quotes of a Mermaid label,
+/// using its entity codes
+fn escape_mermaid(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("#quot;"),
+            '#' => res.push_str("#35;"),
+            '<' => res.push_str("#lt;"),
+            '>' => res.push_str("#gt;"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+// === Tests ===
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dag::xmcs2;
+
+    fn export<T: Display + Copy>(dag: &Dag<'_, T>, format: Format) -> String {
+        let mut res = Vec::new();
+        dag.export(format, &mut res).unwrap();
+        String::from_utf8(res).unwrap()
+    }
+
+    #[test]
+    fn test_export() {
+        let seq = ['"', '<', '#'];
+        let dag = Dag::singleton(2, &seq);
+
+        assert_eq!(
+            r#"{
+  "len": 2,
+  "start": 0,
+  "nodes": [
+    {"id": 0, "type": "end", "max_length": 3, "min_length": 3, "label": "\"<#"}
+  ],
+  "edges": [
+  ]
+}
+"#,
+            export(&dag, Format::Json)
+        );
+        assert!(export(&dag, Format::GraphMl).contains(r#"<node id="n0">"#));
+        assert!(export(&dag, Format::GraphMl).contains(r#"<data key="label">&quot;&lt;#</data>"#));
+        assert_eq!(
+            "flowchart LR\n    start([start]) --> n0\n    n0[\"#quot;#lt;#35;\"]\n",
+            export(&dag, Format::Mermaid)
+        );
+        assert!(export(&dag, Format::Dot).contains(r#"node_0 [label = "\"<#"];"#));
+    }
+
+    #[test]
+    fn test_export_edges() {
+        let dag = xmcs2(3, b"ABCD", b"ACBD");
+        let json = export(&dag, Format::Json);
+        let nodes = json.matches("\"id\"").count();
+        let edges = json.matches("\"source\"").count();
+        assert!(nodes > 1 && nodes <= dag.node_count());
+
+        // All the formats give the same nodes and edges, plus the edge
+        // to the first node
+        let graphml = export(&dag, Format::GraphMl);
+        assert_eq!(nodes, graphml.matches("<node ").count());
+        assert_eq!(edges, graphml.matches("<edge ").count());
+        let mermaid = export(&dag, Format::Mermaid);
+        assert_eq!(nodes + 2, mermaid.lines().count() - edges);
+        assert_eq!(edges + 1, mermaid.matches("-->").count());
+
+        let mut dot = Vec::new();
+        dag.format_graph(&mut dot).unwrap();
+        assert_eq!(String::from_utf8(dot).unwrap(), export(&dag, Format::Dot));
+    }
+}
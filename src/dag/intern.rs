@@ -0,0 +1,236 @@
+//! Sharing of the structurally equal nodes of a graph
+//!
+//! The builders reach the same subgraph from different positions, and
+//! would insert a new node with the same content for each of them. The
+//! nodes are hash-consed instead: before inserting a node, the table
+//! looks for an existing equal node and returns its index. Split and
+//! element nodes are equal if they have the same content, and since
+//! their children are themselves shared, they are then structurally
+//! equal.
+//!
+//! End nodes are only shared by identity, when their suffixes have the
+//! same address and length: equal suffixes at different places of the
+//! sequences give distinct nodes, whose positions in the sequences
+//! differ (see [`Ends`](super::Ends)). All the empty suffixes are shared,
+//! wherever they point.
+//!
+//! The elements are only required to implement `Eq`, so the element
+//! nodes are indexed by their child and the candidates are compared.
+
+use super::memo::{map_bytes, PositionHasher};
+use super::{Node, NodeType, Nodes};
+
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+use std::mem::size_of;
+
+type Map<K, V> = HashMap<K, V, BuildHasherDefault<PositionHasher>>;
+
+/// Index of the nodes of a graph by their content
+#[derive(Default)]
+pub(super) struct Interner {
+    /// Split nodes, by their children
+    splits: Map<(usize, usize), usize>,
+    /// Element nodes, by their child
+    elements: Map<usize, Vec<usize>>,
+    /// End nodes, by the address and the length of their suffix
+    ends: Map<(usize, usize), usize>,
+    /// Number of nodes that were not inserted because an equal node
+    /// already existed
+    shared: usize,
+}
+
+impl Interner {
+    /// Returns the index of a node of `nodes` equal to `node`, inserting
+    /// it if there is none, or `None` if it cannot be inserted (see
+    /// [`Nodes::push`])
+    pub(super) fn insert<'a, T>(
+        &mut self,
+        nodes: &mut Nodes<'a, T>,
+        node: Node<'a, T>,
+    ) -> Option<usize>
+    where
+        T: Eq + Copy,
+    {
+        let existing = match node.inner {
+            NodeType::Empty => None,
+            NodeType::Split { child1, child2 } => self.splits.get(&(child1, child2)).copied(),
+            NodeType::Element { ref value, child } => {
+                self.elements.get(&child).and_then(|candidates| {
+                    candidates.iter().copied().find(|&i| {
+                        matches!(nodes.get(i).inner, NodeType::Element { value: v, .. } if v == *value)
+                    })
+                })
+            }
+            NodeType::End { suffix } => self.ends.get(&end_key(suffix)).copied(),
+        };
+        if let Some(i) = existing {
+            self.shared += 1;
+            return Some(i);
+        }
+
+        // Indexed only once inserted, a node that could not be inserted
+        // must not be found later
+        match node.inner {
+            NodeType::Empty => nodes.push(node),
+            NodeType::Split { child1, child2 } => {
+                let index = nodes.push(node)?;
+                self.splits.insert((child1, child2), index);
+                Some(index)
+            }
+            NodeType::Element { child, .. } => {
+                let index = nodes.push(node)?;
+                self.elements.entry(child).or_default().push(index);
+                Some(index)
+            }
+            NodeType::End { suffix } => {
+                let key = end_key(suffix);
+                let index = nodes.push(node)?;
+                self.ends.insert(key, index);
+                Some(index)
+            }
+        }
+    }
+
+    /// Estimated number of bytes allocated
+    pub(super) fn bytes(&self) -> usize {
+        map_bytes(&self.splits)
+            + map_bytes(&self.elements)
+            + map_bytes(&self.ends)
+            + self.elements.len() * size_of::<usize>()
+    }
+
+    /// Number of nodes that were not inserted because an equal node
+    /// already existed
+    pub(super) const fn shared(&self) -> usize {
+        self.shared
+    }
+}
+
+/// Key of an End node: the address and the length of its suffix
+fn end_key<T>(suffix: &[T]) -> (usize, usize) {
+    // All the empty suffixes are equal, wherever they point
+    if suffix.is_empty() {
+        (0, 0)
+    } else {
+        (suffix.as_ptr() as usize, suffix.len())
+    }
+}
+
+// === Tests ===
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn node<T>(inner: NodeType<'_, T>) -> Node<'_, T> {
+        Node {
+            max_length: 0,
+            min_length: 0,
+            inner,
+        }
+    }
+
+    #[test]
+    fn test_interner() {
+        let seq = b"ABAB";
+        let mut interner = Interner::default();
+        let mut nodes = Nodes::default();
+
+        let end = interner.insert(&mut nodes, node(NodeType::End { suffix: &seq[2..] }));
+        assert_eq!(
+            end,
+            interner.insert(&mut nodes, node(NodeType::End { suffix: &seq[2..] }))
+        );
+        // Same content at another address, not detected
+        assert_ne!(
+            end,
+            interner.insert(&mut nodes, node(NodeType::End { suffix: &seq[..2] }))
+        );
+
+        let empty = interner.insert(&mut nodes, node(NodeType::End { suffix: &seq[4..] }));
+        assert_eq!(
+            empty,
+            interner.insert(&mut nodes, node(NodeType::End { suffix: &[] }))
+        );
+
+        let a = interner.insert(
+            &mut nodes,
+            node(NodeType::Element {
+                value: b'A',
+                child: end.unwrap(),
+            }),
+        );
+        let b = interner.insert(
+            &mut nodes,
+            node(NodeType::Element {
+                value: b'B',
+                child: end.unwrap(),
+            }),
+        );
+        assert_ne!(a, b);
+        assert_eq!(
+            b,
+            interner.insert(
+                &mut nodes,
+                node(NodeType::Element {
+                    value: b'B',
+                    child: end.unwrap()
+                })
+            )
+        );
+
+        let split = interner.insert(
+            &mut nodes,
+            node(NodeType::Split {
+                child1: a.unwrap(),
+                child2: b.unwrap(),
+            }),
+        );
+        assert_eq!(
+            split,
+            interner.insert(
+                &mut nodes,
+                node(NodeType::Split {
+                    child1: a.unwrap(),
+                    child2: b.unwrap()
+                })
+            )
+        );
+        assert_ne!(
+            split,
+            interner.insert(
+                &mut nodes,
+                node(NodeType::Split {
+                    child1: b.unwrap(),
+                    child2: a.unwrap()
+                })
+            )
+        );
+
+        assert_eq!(7, nodes.len());
+        assert_eq!(4, interner.shared());
+    }
+
+    #[cfg(feature = "compact")]
+    #[test]
+    fn test_failed_insert() {
+        let seq = b"AB";
+        let mut interner = Interner::default();
+        let mut nodes = Nodes::default();
+
+        // A length too large for the compact nodes
+        let mut end = node(NodeType::End { suffix: &seq[..] });
+        end.max_length = usize::MAX;
+        assert_eq!(None, interner.insert(&mut nodes, end));
+
+        // Another node takes the index the failed node would have had,
+        // and the failed node is not found
+        let other = interner.insert(&mut nodes, node(NodeType::End { suffix: &seq[1..] }));
+        assert_eq!(Some(0), other);
+        let end = interner.insert(&mut nodes, node(NodeType::End { suffix: &seq[..] }));
+        assert_eq!(Some(1), end);
+        assert_eq!(2, nodes.len());
+        assert_eq!(0, interner.shared());
+    }
+}
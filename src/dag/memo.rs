@@ -0,0 +1,379 @@
+//! Memoisation tables of the builders
+//!
+//! The builders remember the node computed for each position they
+//! visit. Hashing the positions dominates the run time, so when the
+//! positions that can be reached fit in a small enough band, they are
+//! stored in a dense array instead. This array is allocated in chunks,
+//! when a position of the chunk is first stored: the parts of the band
+//! that are never visited take no memory.
+//!
+//! Only the positions where the minimum length `len` is at most the
+//! length of both tails are stored. As `len` only decreases by one for
+//! each matched element, it lies in a band of width `Δ + 1` for each
+//! position `j` in the second sequence, where `Δ` is the difference
+//! between the length of the second sequence and the initial `len`.
+
+use super::Position;
+
+use std::cmp::{max, min};
+use std::collections::HashMap;
+use std::hash::{BuildHasherDefault, Hasher};
+use std::mem::size_of;
+
+/// Number of entries of a dense table allowed for each pair of
+/// positions in the inputs: the positions visited are a small part of
+/// the band when it is deep, and a hash table is then smaller
+const DENSE_FACTOR: usize = 4;
+
+/// Number of entries of a dense table always allowed, however small the
+/// inputs are
+const DENSE_MIN: usize = 1 << 16;
+
+/// Maximum number of entries of a dense table, larger tables fall back
+/// to a hash table
+const DENSE_LIMIT: usize = 1 << 24;
+
+/// Base 2 logarithm of the number of entries of a chunk of a dense table
+const CHUNK_BITS: usize = 12;
+
+/// Table remembering the index of the node computed for each position,
+/// or `None` if it represents the empty set
+pub(super) enum Memo {
+    /// Array indexed by the positions in the band
+    Dense(Dense),
+    /// Hash table, used when the band is too large
+    Sparse(HashMap<Position, Option<usize>, BuildHasherDefault<PositionHasher>>),
+}
+
+/// Dense table over the positions `Position(len, row, l2, 0)` reachable
+/// from an initial minimum length
+pub(super) struct Dense {
+    /// Initial minimum length
+    len: usize,
+    /// Length of the second sequence
+    d2: usize,
+    /// Meaning of the second field of the positions
+    rows: Rows,
+    /// Number of columns of a row
+    cols: usize,
+    /// Number of minimum lengths stored for each row and column
+    depth: usize,
+    /// Number of entries of the table
+    size: usize,
+    /// Chunks of `2^CHUNK_BITS` entries of the table, allocated when
+    /// first written: `0` if the position was not computed yet, `1` for
+    /// the empty set and `index + 2` for the node at `index`
+    chunks: Vec<Option<Box<[usize]>>>,
+    /// Number of entries of the chunks allocated
+    allocated: usize,
+    /// Number of positions stored in `chunks`
+    entries: usize,
+    /// Positions outside of the band, which should not be reached
+    spill: HashMap<Position, Option<usize>, BuildHasherDefault<PositionHasher>>,
+}
+
+/// Meaning of the second field of the positions
+enum Rows {
+    /// Length of the tail of a first sequence of length `d1`, the columns
+    /// are the positions in the second sequence at distance at most
+    /// `delta` from the position in the first one
+    Sequence { d1: usize, delta: usize },
+    /// Index of a node of a graph, the columns are the positions in the
+    /// second sequence
+    Graph,
+}
+
+impl Memo {
+    /// Hash table, for positions with no known structure
+    pub(super) fn sparse() -> Self {
+        Self::Sparse(HashMap::default())
+    }
+
+    /// Table for the positions in two sequences of lengths `d1` and `d2`
+    /// reachable from the minimum length `len`
+    ///
+    /// The dense table is only used if `allows` accepts its full size in
+    /// bytes, a hash table is used otherwise.
+    pub(super) fn sequences(
+        len: usize,
+        d1: usize,
+        d2: usize,
+        allows: impl FnOnce(usize) -> bool,
+    ) -> Self {
+        let delta = max(d1, d2).saturating_sub(len);
+        let rows = Rows::Sequence { d1, delta };
+        let pairs = (d1 + 1).saturating_mul(d2 + 1);
+        Self::dense(len, d2, rows, (d1 + 1, 2 * delta + 1), pairs, allows)
+    }
+
+    /// Table for the positions in a graph of `nodes` nodes and a
+    /// sequence of length `d2` reachable from the minimum length `len`
+    ///
+    /// The dense table is only used if `allows` accepts its full size in
+    /// bytes, a hash table is used otherwise.
+    pub(super) fn graph(
+        len: usize,
+        nodes: usize,
+        d2: usize,
+        allows: impl FnOnce(usize) -> bool,
+    ) -> Self {
+        let pairs = nodes.saturating_mul(d2 + 1);
+        Self::dense(len, d2, Rows::Graph, (nodes, d2 + 1), pairs, allows)
+    }
+
+    /// Dense table of `count` rows of `cols` columns, if it has at most
+    /// `DENSE_FACTOR` entries for each of the `pairs` pairs of positions
+    /// in the inputs and its size is accepted by `allows`
+    fn dense(
+        len: usize,
+        d2: usize,
+        rows: Rows,
+        (count, cols): (usize, usize),
+        pairs: usize,
+        allows: impl FnOnce(usize) -> bool,
+    ) -> Self {
+        let depth = min(d2.saturating_sub(len), len) + 1;
+        let limit = pairs
+            .saturating_mul(DENSE_FACTOR)
+            .clamp(DENSE_MIN, DENSE_LIMIT);
+        let size = count
+            .checked_mul(cols)
+            .and_then(|size| size.checked_mul(depth))
+            .filter(|&size| size <= limit);
+
+        match size {
+            Some(size) if allows(size * size_of::<usize>()) => Self::Dense(Dense {
+                len,
+                d2,
+                rows,
+                cols,
+                depth,
+                size,
+                chunks: (0..size.div_ceil(1 << CHUNK_BITS)).map(|_| None).collect(),
+                allocated: 0,
+                entries: 0,
+                spill: HashMap::default(),
+            }),
+            _ => Self::sparse(),
+        }
+    }
+
+    /// Returns the value stored for `pos`, or `None` if it was not
+    /// computed yet
+    #[inline(always)]
+    #[allow(clippy::option_option)]
+    pub(super) fn get(&self, pos: Position) -> Option<Option<usize>> {
+        match self {
+            Self::Dense(dense) => dense.index(pos).map_or_else(
+                || dense.spill.get(&pos).copied(),
+                |index| match dense.value(index) {
+                    0 => None,
+                    value => Some(value.checked_sub(2)),
+                },
+            ),
+            Self::Sparse(map) => map.get(&pos).copied(),
+        }
+    }
+
+    /// Store the value computed for `pos`
+    #[inline(always)]
+    pub(super) fn insert(&mut self, pos: Position, value: Option<usize>) {
+        match self {
+            Self::Dense(dense) => match dense.index(pos) {
+                Some(index) => {
+                    let entry = dense.entry(index);
+                    let new = *entry == 0;
+                    *entry = value.map_or(1, |v| v + 2);
+                    dense.entries += usize::from(new);
+                }
+                None => {
+                    dense.spill.insert(pos, value);
+                }
+            },
+            Self::Sparse(map) => {
+                map.insert(pos, value);
+            }
+        }
+    }
+
+    /// Number of positions stored
+    pub(super) fn len(&self) -> usize {
+        match self {
+            Self::Dense(dense) => dense.entries + dense.spill.len(),
+            Self::Sparse(map) => map.len(),
+        }
+    }
+
+    /// Estimated number of bytes allocated
+    pub(super) fn bytes(&self) -> usize {
+        match self {
+            Self::Dense(dense) => dense.bytes() + map_bytes(&dense.spill),
+            Self::Sparse(map) => map_bytes(map),
+        }
+    }
+}
+
+/// Estimated number of bytes allocated by a hash table, one control
+/// byte being used by each entry
+pub(super) fn map_bytes<K, V, S>(map: &HashMap<K, V, S>) -> usize {
+    map.capacity() * (size_of::<(K, V)>() + 1)
+}
+
+impl Dense {
+    /// Index of `pos` in the table, `None` if it is outside of the band
+    #[inline(always)]
+    fn index(&self, Position(len, row, l2, gap): Position) -> Option<usize> {
+        if gap != 0 || l2 > self.d2 {
+            return None;
+        }
+
+        let j = self.d2 - l2;
+        let (row, col) = match self.rows {
+            Rows::Sequence { d1, delta } => {
+                let i = d1.checked_sub(row)?;
+                (i, (j + delta).checked_sub(i)?)
+            }
+            Rows::Graph => (row, j),
+        };
+        let depth = len.checked_sub(self.len.saturating_sub(j))?;
+
+        if col < self.cols && depth < self.depth {
+            let index = (row * self.cols + col) * self.depth + depth;
+            (index < self.size).then_some(index)
+        } else {
+            None
+        }
+    }
+
+    /// Value stored at `index`, `0` if its chunk is not allocated
+    #[inline(always)]
+    fn value(&self, index: usize) -> usize {
+        self.chunks[index >> CHUNK_BITS]
+            .as_ref()
+            .map_or(0, |chunk| chunk[index & ((1 << CHUNK_BITS) - 1)])
+    }
+
+    /// Entry at `index`, allocating its chunk if needed
+    #[inline(always)]
+    fn entry(&mut self, index: usize) -> &mut usize {
+        let start = index >> CHUNK_BITS << CHUNK_BITS;
+        let len = min(1 << CHUNK_BITS, self.size - start);
+        let allocated = &mut self.allocated;
+        let chunk = self.chunks[index >> CHUNK_BITS].get_or_insert_with(|| {
+            *allocated += len;
+            vec![0; len].into_boxed_slice()
+        });
+        &mut chunk[index - start]
+    }
+
+    /// Estimated number of bytes allocated for the chunks
+    fn bytes(&self) -> usize {
+        self.allocated * size_of::<usize>()
+            + self.chunks.capacity() * size_of::<Option<Box<[usize]>>>()
+    }
+}
+
+/// Hasher for the positions, much faster than the default one
+///
+/// The positions are small integers and are not chosen by an adversary,
+/// so the resistance to collisions of the default hasher is not needed.
+#[derive(Debug, Default, Clone, Copy)]
+pub(super) struct PositionHasher(u64);
+
+impl PositionHasher {
+    #[inline(always)]
+    fn add(&mut self, value: u64) {
+        self.0 = (self.0.rotate_left(5) ^ value).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+}
+
+impl Hasher for PositionHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.add(u64::from(b));
+        }
+    }
+
+    #[inline(always)]
+    fn write_usize(&mut self, i: usize) {
+        self.add(i as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// === Tests ===
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_memo() {
+        let mut dense = Memo::sequences(3, 5, 6, |_| true);
+        let mut sparse = Memo::sparse();
+        assert!(matches!(dense, Memo::Dense(_)));
+
+        // Positions in the band, and outside of it
+        let positions = [
+            Position(3, 5, 6, 0),
+            Position(2, 3, 4, 0),
+            Position(0, 0, 2, 0),
+            Position(1, 1, 1, 0),
+            Position(3, 5, 6, 1),
+            Position(0, 6, 7, 0),
+        ];
+
+        for memo in [&mut dense, &mut sparse] {
+            for (i, &pos) in positions.iter().enumerate() {
+                assert_eq!(None, memo.get(pos));
+                let value = if i % 2 == 0 { Some(i) } else { None };
+                memo.insert(pos, value);
+                assert_eq!(Some(value), memo.get(pos));
+            }
+            assert_eq!(positions.len(), memo.len());
+            memo.insert(positions[0], None);
+            assert_eq!(positions.len(), memo.len());
+        }
+    }
+
+    #[test]
+    fn test_dense_limits() {
+        // 6 rows of 7 columns of depth 4
+        let bytes = 6 * 7 * 4 * size_of::<usize>();
+        let mut memo = Memo::sequences(3, 5, 6, |size| {
+            assert_eq!(bytes, size);
+            true
+        });
+        // Allocated when first written, in a single chunk
+        assert!(memo.bytes() < bytes);
+        memo.insert(Position(3, 5, 6, 0), None);
+        assert_eq!(bytes + 16, memo.bytes());
+
+        // Only the chunks written are allocated, out of more than 1 MB
+        let mut memo = Memo::sequences(990, 1000, 1000, |size| size > 1 << 20);
+        assert!(matches!(memo, Memo::Dense(_)));
+        assert!(memo.bytes() < 1 << 10);
+        memo.insert(Position(990, 1000, 1000, 0), Some(0));
+        memo.insert(Position(989, 999, 999, 0), Some(1));
+        assert!(memo.bytes() < 1 << 16);
+        assert_eq!(Some(Some(1)), memo.get(Position(989, 999, 999, 0)));
+        assert_eq!(None, memo.get(Position(0, 0, 0, 0)));
+
+        // Too large for the budget, checked before allocating
+        let memo = Memo::sequences(3, 5, 6, |size| size < bytes);
+        assert!(matches!(memo, Memo::Sparse(_)));
+
+        // Deep band over short inputs, below the minimum
+        let memo = Memo::sequences(10, 30, 30, |_| true);
+        assert!(matches!(memo, Memo::Dense(_)));
+
+        // Deep band over longer inputs, most of it would not be visited
+        let memo = Memo::sequences(100, 200, 200, |_| panic!("allocated"));
+        assert!(matches!(memo, Memo::Sparse(_)));
+        let memo = Memo::graph(100, 200, 200, |_| panic!("allocated"));
+        assert!(matches!(memo, Memo::Sparse(_)));
+    }
+}
@@ -0,0 +1,354 @@
+//! Storage of the nodes of a graph
+//!
+//! By default the nodes are stored as they are, with `usize` indices and
+//! lengths and a slice for the suffix of the End nodes. With the
+//! `compact` feature, the indices and lengths are stored as `u32`, and
+//! the suffixes as an index in a pool of slices and a length: all the
+//! suffixes ending at the same address are tails of the longest one,
+//! which is the only slice stored. This halves the size of the nodes,
+//! and limits the graphs to [`MAX_NODES`] nodes: [`Nodes::push`] then
+//! fails, and the builders return [`Error::BudgetExceeded`].
+//!
+//! [`Error::BudgetExceeded`]: crate::Error::BudgetExceeded
+//!
+//! Either way, the nodes are read and written as [`Node`]s.
+
+use super::Node;
+
+#[cfg(feature = "compact")]
+use super::memo::{map_bytes, PositionHasher};
+#[cfg(feature = "compact")]
+use super::NodeType;
+
+#[cfg(feature = "compact")]
+use std::collections::HashMap;
+#[cfg(feature = "compact")]
+use std::convert::TryFrom;
+#[cfg(feature = "compact")]
+use std::hash::BuildHasherDefault;
+use std::mem::size_of;
+
+/// Maximum number of nodes of a graph, checked by [`Nodes::push`]
+#[cfg(feature = "compact")]
+pub(super) const MAX_NODES: usize = 1 << 31;
+
+/// Array of nodes
+#[cfg(not(feature = "compact"))]
+pub(super) struct Nodes<'a, T> {
+    array: Vec<Node<'a, T>>,
+}
+
+/// Array of nodes, stored with `u32` indices
+#[cfg(feature = "compact")]
+pub(super) struct Nodes<'a, T> {
+    array: Vec<Packed<T>>,
+    /// Longest suffix ending at each address
+    suffixes: Vec<&'a [T]>,
+    /// Index in `suffixes` of the suffix ending at each address
+    ends: HashMap<usize, u32, BuildHasherDefault<PositionHasher>>,
+}
+
+/// Node stored with `u32` indices and lengths
+#[cfg(feature = "compact")]
+struct Packed<T> {
+    max_length: u32,
+    min_length: u32,
+    inner: PackedType<T>,
+}
+
+#[cfg(feature = "compact")]
+enum PackedType<T> {
+    Empty,
+    /// The last `len` elements of a suffix of the pool
+    End {
+        suffix: u32,
+        len: u32,
+    },
+    Split {
+        child1: u32,
+        child2: u32,
+    },
+    Element {
+        value: T,
+        child: u32,
+    },
+}
+
+impl<T> Default for Nodes<'_, T> {
+    #[cfg(not(feature = "compact"))]
+    fn default() -> Self {
+        Self { array: Vec::new() }
+    }
+
+    #[cfg(feature = "compact")]
+    fn default() -> Self {
+        Self {
+            array: Vec::new(),
+            suffixes: Vec::new(),
+            ends: HashMap::default(),
+        }
+    }
+}
+
+impl<T> Nodes<'_, T> {
+    /// Number of nodes
+    pub(super) fn len(&self) -> usize {
+        self.array.len()
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.array.is_empty()
+    }
+
+    /// Estimated number of bytes allocated
+    #[cfg(not(feature = "compact"))]
+    pub(super) fn bytes(&self) -> usize {
+        self.array.capacity() * size_of::<Node<'_, T>>()
+    }
+
+    /// Estimated number of bytes allocated
+    #[cfg(feature = "compact")]
+    pub(super) fn bytes(&self) -> usize {
+        self.array.capacity() * size_of::<Packed<T>>()
+            + self.suffixes.capacity() * size_of::<&[T]>()
+            + map_bytes(&self.ends)
+    }
+}
+
+#[cfg(not(feature = "compact"))]
+impl<'a, T> Nodes<'a, T> {
+    /// Appends `node`, returns its index
+    ///
+    /// Never fails, unlike with the `compact` feature.
+    #[allow(clippy::unnecessary_wraps)]
+    pub(super) fn push(&mut self, node: Node<'a, T>) -> Option<usize> {
+        self.array.push(node);
+        Some(self.array.len() - 1)
+    }
+}
+
+#[cfg(not(feature = "compact"))]
+impl<'a, T> Nodes<'a, T>
+where
+    T: Copy,
+{
+    /// Node at `index`
+    pub(super) fn get(&self, index: usize) -> Node<'a, T> {
+        self.array[index].clone()
+    }
+}
+
+#[cfg(feature = "compact")]
+impl<'a, T> Nodes<'a, T> {
+    /// Appends `node`, returns its index
+    ///
+    /// Returns `None`, leaving the nodes unchanged, if there are already
+    /// [`MAX_NODES`] nodes or if a length does not fit in a `u32`.
+    pub(super) fn push(&mut self, node: Node<'a, T>) -> Option<usize> {
+        if self.array.len() >= MAX_NODES {
+            return None;
+        }
+
+        let max_length = compact(node.max_length)?;
+        let min_length = compact(node.min_length)?;
+        // The children and the suffixes of the pool have smaller indices
+        // than the new node
+        let inner = match node.inner {
+            NodeType::Empty => PackedType::Empty,
+            NodeType::End { suffix } => PackedType::End {
+                len: compact(suffix.len())?,
+                suffix: self.pool(suffix)?,
+            },
+            NodeType::Split { child1, child2 } => PackedType::Split {
+                child1: compact(child1)?,
+                child2: compact(child2)?,
+            },
+            NodeType::Element { value, child } => PackedType::Element {
+                value,
+                child: compact(child)?,
+            },
+        };
+
+        self.array.push(Packed {
+            max_length,
+            min_length,
+            inner,
+        });
+        Some(self.array.len() - 1)
+    }
+
+    /// Index in the pool of a suffix ending where `suffix` ends, at least
+    /// as long
+    fn pool(&mut self, suffix: &'a [T]) -> Option<u32> {
+        // Empty suffixes all end at the same address
+        let end = if suffix.is_empty() {
+            0
+        } else {
+            suffix.as_ptr_range().end as usize
+        };
+
+        let next = compact(self.suffixes.len())?;
+        let suffixes = &mut self.suffixes;
+        let index = *self.ends.entry(end).or_insert_with(|| {
+            suffixes.push(suffix);
+            next
+        });
+
+        // Longer tail of the same sequence, the suffixes already stored
+        // are tails of this one
+        let pooled = &mut self.suffixes[index as usize];
+        if pooled.len() < suffix.len() {
+            *pooled = suffix;
+        }
+        Some(index)
+    }
+}
+
+#[cfg(feature = "compact")]
+impl<'a, T> Nodes<'a, T>
+where
+    T: Copy,
+{
+    /// Node at `index`
+    pub(super) fn get(&self, index: usize) -> Node<'a, T> {
+        let packed = &self.array[index];
+        let inner = match packed.inner {
+            PackedType::Empty => NodeType::Empty,
+            PackedType::End { suffix, len } => {
+                let pooled = self.suffixes[suffix as usize];
+                NodeType::End {
+                    suffix: &pooled[pooled.len() - len as usize..],
+                }
+            }
+            PackedType::Split { child1, child2 } => NodeType::Split {
+                child1: child1 as usize,
+                child2: child2 as usize,
+            },
+            PackedType::Element { value, child } => NodeType::Element {
+                value,
+                child: child as usize,
+            },
+        };
+
+        Node {
+            max_length: packed.max_length as usize,
+            min_length: packed.min_length as usize,
+            inner,
+        }
+    }
+}
+
+impl<'a, T> Nodes<'a, T>
+where
+    T: Copy,
+{
+    /// Iterates over the nodes, in the order of their indices
+    pub(super) fn iter(&self) -> impl Iterator<Item = Node<'a, T>> + '_ {
+        (0..self.len()).map(move |i| self.get(i))
+    }
+}
+
+/// Converts an index or a length to its compact representation, if it
+/// fits
+#[cfg(feature = "compact")]
+fn compact(value: usize) -> Option<u32> {
+    u32::try_from(value).ok()
+}
+
+// === Tests ===
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dag::NodeType;
+
+    fn node<T>(max_length: usize, inner: NodeType<'_, T>) -> Node<'_, T> {
+        Node {
+            max_length,
+            min_length: 1,
+            inner,
+        }
+    }
+
+    #[test]
+    fn test_nodes() {
+        let seq = b"ABCD";
+        let mut nodes = Nodes::default();
+
+        let short = nodes
+            .push(node(2, NodeType::End { suffix: &seq[2..] }))
+            .unwrap();
+        let long = nodes
+            .push(node(3, NodeType::End { suffix: &seq[1..] }))
+            .unwrap();
+        let other = nodes
+            .push(node(2, NodeType::End { suffix: &seq[..2] }))
+            .unwrap();
+        let empty = nodes.push(node(0, NodeType::End { suffix: &[] })).unwrap();
+        let element = nodes
+            .push(node(
+                3,
+                NodeType::Element {
+                    value: b'A',
+                    child: short,
+                },
+            ))
+            .unwrap();
+        let split = nodes
+            .push(node(
+                4,
+                NodeType::Split {
+                    child1: element,
+                    child2: long,
+                },
+            ))
+            .unwrap();
+        nodes.push(node(0, NodeType::Empty));
+
+        assert_eq!(7, nodes.len());
+        let suffixes = nodes
+            .iter()
+            .filter_map(|n| match n.inner {
+                NodeType::End { suffix } => Some(suffix),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(vec![&b"CD"[..], b"BCD", b"AB", b""], suffixes);
+        // Same addresses as the original slices
+        assert_eq!(seq[1..].as_ptr(), suffixes[1].as_ptr());
+        assert_eq!(seq.as_ptr(), suffixes[2].as_ptr());
+
+        assert_eq!(3, nodes.get(long).max_length);
+        assert_eq!(1, nodes.get(other).min_length);
+        assert_eq!(0, nodes.get(empty).max_length);
+        assert!(matches!(
+            nodes.get(element).inner,
+            NodeType::Element { value: b'A', child } if child == short
+        ));
+        assert!(matches!(
+            nodes.get(split).inner,
+            NodeType::Split { child1, child2 } if child1 == element && child2 == long
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "compact")]
+    fn test_compact_push() {
+        let seq = b"ABCD";
+        let mut nodes = Nodes::default();
+
+        // The lengths do not fit in a `u32`
+        assert_eq!(None, nodes.push(node(usize::MAX, NodeType::Empty)));
+        let end = NodeType::End { suffix: &seq[..] };
+        assert_eq!(None, nodes.push(node(usize::MAX, end.clone())));
+        assert!(nodes.is_empty());
+        assert_eq!(Some(0), nodes.push(node(4, end)));
+    }
+
+    #[test]
+    #[cfg(feature = "compact")]
+    fn test_compact_size() {
+        assert!(2 * size_of::<Packed<u8>>() <= size_of::<Node<'_, u8>>());
+        assert!(2 * size_of::<Packed<char>>() <= size_of::<Node<'_, char>>());
+    }
+}
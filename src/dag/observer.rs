@@ -0,0 +1,167 @@
+//! Progress reporting and cancellation of the builders
+//!
+//! Building the graph of many long sequences can take minutes. An
+//! [`Observer`] is notified after each sequence is added to the graph,
+//! and periodically while a sequence is being added, and can stop the
+//! computation by returning [`Control::Cancel`].
+
+use super::budget::{Budget, Usage};
+
+use crate::Error;
+
+/// Number of new positions computed by a builder between two
+/// notifications of the observer
+const PERIOD: usize = 1 << 16;
+
+/// Whether the computation should go on, returned by an [`Observer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[must_use]
+pub enum Control {
+    /// Go on with the computation
+    Continue,
+    /// Stop the computation, which returns [`Error::Cancelled`]
+    Cancel,
+}
+
+/// State of a computation, given to an [`Observer`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Index of the sequence being added to the graph
+    pub sequence: usize,
+    /// Number of sequences
+    pub sequences: usize,
+    /// Whether the sequence is fully added, `false` for the periodic
+    /// notifications
+    pub added: bool,
+    /// Number of nodes of the graph being built
+    pub nodes: usize,
+    /// Number of positions stored in the memoisation tables
+    pub memo_entries: usize,
+    /// Estimated number of bytes allocated
+    pub bytes: usize,
+}
+
+/// Receives the progress of a computation
+///
+/// It is implemented by the closures taking a [`Progress`].
+///
+/// # Examples
+/// ```
+/// # use xmcs::dag::{try_xmcsk_observed, Budget, Control, Progress};
+/// let seqs = [&b"ADBCBAD"[..], b"ADCBACD", b"ABDCABDA"];
+///
+/// let mut added = Vec::new();
+/// let mut observer = |p: &Progress| {
+///     if p.added {
+///         added.push(p.sequence);
+///     }
+///     Control::Continue
+/// };
+/// let dag = try_xmcsk_observed(2, &seqs, &Budget::default(), &mut observer).unwrap();
+///
+/// assert_eq!(Some(5), dag.extract_lcs().map(|s| s.len()));
+/// assert_eq!(vec![0, 1, 2], added);
+/// ```
+pub trait Observer {
+    /// Called with the current state of the computation, returns whether
+    /// it should go on
+    fn notify(&mut self, progress: &Progress) -> Control;
+}
+
+impl<F> Observer for F
+where
+    F: FnMut(&Progress) -> Control,
+{
+    fn notify(&mut self, progress: &Progress) -> Control {
+        self(progress)
+    }
+}
+
+/// Budget and observer of a computation, checked by the builders
+pub(super) struct Monitor<'o> {
+    /// Limits on the resources used
+    budget: Budget,
+    /// Whether `budget` was set by the caller, and is checked for each
+    /// new position
+    limited: bool,
+    /// Notified of the progress, if any
+    observer: Option<&'o mut dyn Observer>,
+    /// Index of the sequence being added
+    pub(super) sequence: usize,
+    /// Number of sequences
+    sequences: usize,
+    /// Number of new positions left before the next notification
+    countdown: usize,
+}
+
+impl<'o> Monitor<'o> {
+    pub(super) fn new(
+        budget: Budget,
+        observer: Option<&'o mut dyn Observer>,
+        sequences: usize,
+    ) -> Self {
+        Self {
+            budget: budget.capped(),
+            limited: budget != Budget::default(),
+            observer,
+            sequence: 0,
+            sequences,
+            countdown: PERIOD,
+        }
+    }
+
+    /// No limit and no observer
+    pub(super) fn unlimited(sequences: usize) -> Self {
+        Self::new(Budget::default(), None, sequences)
+    }
+
+    /// Returns whether a table of `bytes` bytes fits in the budget
+    pub(super) fn allows_bytes(&self, bytes: usize) -> bool {
+        self.budget.allows_bytes(bytes)
+    }
+
+    /// Called by the builders for each new position, with the resources
+    /// they use computed by `usage` only if needed
+    pub(super) fn step(&mut self, usage: impl FnOnce() -> Usage) -> Result<(), Error> {
+        let periodic = self.observer.is_some() && {
+            self.countdown -= 1;
+            self.countdown == 0
+        };
+        if !periodic && !self.limited {
+            return Ok(());
+        }
+
+        let usage = usage();
+        self.budget.check(self.sequence, usage)?;
+        if periodic {
+            self.countdown = PERIOD;
+            self.notify(usage, false)?;
+        }
+        Ok(())
+    }
+
+    /// Called once the current sequence is added, with the resources
+    /// used to add it
+    pub(super) fn added(&mut self, usage: Usage) -> Result<(), Error> {
+        self.budget.check(self.sequence, usage)?;
+        self.notify(usage, true)
+    }
+
+    fn notify(&mut self, usage: Usage, added: bool) -> Result<(), Error> {
+        let progress = Progress {
+            sequence: self.sequence,
+            sequences: self.sequences,
+            added,
+            nodes: usage.nodes,
+            memo_entries: usage.memo_entries,
+            bytes: usage.bytes,
+        };
+
+        match self.observer.as_mut().map(|o| o.notify(&progress)) {
+            Some(Control::Cancel) => Err(Error::Cancelled {
+                sequence: self.sequence,
+            }),
+            _ => Ok(()),
+        }
+    }
+}
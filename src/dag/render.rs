@@ -1,18 +1,155 @@
 //! Module used to format graphs to visualize them with the
 //! graphviz tools
 //!
-//! The functions in this modules are not really optimised
-//! I don't care, since the graphviz tool is way slower to
-//! render the graph anyways.
+//! The functions in this module favour simplicity over speed:
+//! rendering the output with graphviz is much slower than writing it.
 
 use super::{Dag, NodeType};
 
+use std::cmp::max;
+use std::collections::HashSet;
 use std::fmt::Display;
 use std::io::{Result, Write};
 
+/// Nodes and edges emphasised in the output of
+/// [`format_graph_highlighted`](Dag::format_graph_highlighted)
+///
+/// # Examples
+/// ```
+/// # use xmcs::dag::{xmcs2, Highlight};
+/// let dag = xmcs2(3, b"ABCD", b"ACBD");
+///
+/// let lcs = dag.extract_lcs().unwrap();
+/// let highlight = Highlight {
+///     path: dag.path_of(&lcs).unwrap(),
+///     dim_short: true,
+/// };
+/// let mut dot = Vec::new();
+/// dag.format_graph_highlighted(&mut dot, &highlight).unwrap();
+/// assert!(String::from_utf8(dot).unwrap().contains("penwidth"));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Highlight {
+    /// Indices of the nodes of a path, coloured along with the edges
+    /// between consecutive nodes (see [`Dag::path_of`])
+    pub path: Vec<usize>,
+    /// Grey out the nodes that are not on a path of the maximum length
+    pub dim_short: bool,
+}
+
+/// Direction of the edges in the drawing (the `rankdir` attribute of
+/// graphviz)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RankDir {
+    /// From left to right
+    #[default]
+    LeftRight,
+    /// From top to bottom
+    TopBottom,
+    /// From right to left
+    RightLeft,
+    /// From bottom to top
+    BottomTop,
+}
+
+impl RankDir {
+    const fn as_str(self) -> &'static str {
+        match self {
+            Self::LeftRight => "LR",
+            Self::TopBottom => "TB",
+            Self::RightLeft => "RL",
+            Self::BottomTop => "BT",
+        }
+    }
+}
+
+/// Formats an element of the sequences in the labels of a drawing
+pub type Formatter<'f, T> = Box<dyn Fn(&T) -> String + 'f>;
+
+/// Options of [`format_graph_with_options`](Dag::format_graph_with_options)
+///
+/// # Examples
+/// ```
+/// # use xmcs::dag::{xmcs2, RankDir, RenderOptions};
+/// let dag = xmcs2(3, b"ABCD", b"ACBD");
+///
+/// let options = RenderOptions {
+///     rank_dir: RankDir::TopBottom,
+///     info: false,
+///     label: Some(Box::new(|&e: &u8| char::from(e).to_string())),
+///     ..RenderOptions::default()
+/// };
+/// let mut dot = Vec::new();
+/// dag.format_graph_with_options(&mut dot, &options).unwrap();
+///
+/// let dot = String::from_utf8(dot).unwrap();
+/// assert!(dot.contains("rankdir = TB;"));
+/// assert!(dot.contains(r#"label = "BD""#));
+/// ```
+pub struct RenderOptions<'f, T> {
+    /// Direction of the edges, from left to right by default
+    pub rank_dir: RankDir,
+    /// Whether to draw a box with the minimum length of the
+    /// subsequences and the number of nodes, `true` by default
+    pub info: bool,
+    /// Formats the elements of the sequences, with [`Display`] if `None`
+    pub label: Option<Formatter<'f, T>>,
+    /// Suffixes longer than this are shortened to their first elements
+    /// and the number of elements left out
+    pub max_suffix: Option<usize>,
+    /// Nodes and edges to emphasise
+    pub highlight: Highlight,
+}
+
+impl<T> Default for RenderOptions<'_, T> {
+    fn default() -> Self {
+        Self {
+            rank_dir: RankDir::default(),
+            info: true,
+            label: None,
+            max_suffix: None,
+            highlight: Highlight::default(),
+        }
+    }
+}
+
+/// Style of the nodes and edges, computed from a [`Highlight`]
+struct Marks {
+    /// Nodes of the highlighted path
+    nodes: HashSet<usize>,
+    /// Edges between consecutive nodes of the path
+    edges: HashSet<(usize, usize)>,
+    /// Whether each node is greyed out
+    dimmed: Vec<bool>,
+}
+
+impl Marks {
+    /// Colour `attribute` of a node, `fontcolor` for the End nodes
+    fn node(&self, index: usize, attribute: &str) -> Option<String> {
+        if self.nodes.contains(&index) {
+            Some(format!("{attribute} = red"))
+        } else if self.dimmed.get(index) == Some(&true) {
+            Some(format!("{attribute} = grey"))
+        } else {
+            None
+        }
+    }
+
+    /// Attributes of an edge
+    fn edge(&self, from: usize, to: usize) -> Option<&'static str> {
+        if self.edges.contains(&(from, to)) {
+            Some("color = red, penwidth = 3")
+        } else if self.dimmed.get(from) == Some(&true) || self.dimmed.get(to) == Some(&true) {
+            Some("color = grey")
+        } else {
+            None
+        }
+    }
+}
+
 impl<T> Dag<'_, T>
 where
-    T: Display,
+    T: Display + Copy,
 {
     /// Outputs code to be used with the [dot] tool to produce
     /// a visualisation of the current graph
@@ -22,157 +159,240 @@ where
     ///
     /// [dot]: (https://graphviz.org/)
     pub fn format_graph(&self, w: &mut impl Write) -> Result<()> {
+        self.format_graph_with_options(w, &RenderOptions::default())
+    }
+
+    /// Same as [`format_graph`](Dag::format_graph), emphasising the
+    /// nodes and edges given by `highlight`
+    ///
+    /// # Errors
+    /// Forwards errors from writing into `w`.
+    pub fn format_graph_highlighted(
+        &self,
+        w: &mut impl Write,
+        highlight: &Highlight,
+    ) -> Result<()> {
+        let options = RenderOptions {
+            highlight: highlight.clone(),
+            ..RenderOptions::default()
+        };
+        self.format_graph_with_options(w, &options)
+    }
+
+    /// Same as [`format_graph`](Dag::format_graph), with the layout and
+    /// the labels given by `options`
+    ///
+    /// # Errors
+    /// Forwards errors from writing into `w`.
+    pub fn format_graph_with_options(
+        &self,
+        w: &mut impl Write,
+        options: &RenderOptions<'_, T>,
+    ) -> Result<()> {
+        let display = |e: &T| e.to_string();
+        let label = options.label.as_deref().unwrap_or(&display);
+        self.render(w, label, options)
+    }
+}
+
+impl<T> Dag<'_, T>
+where
+    T: Copy,
+{
+    /// Same as [`format_graph`](Dag::format_graph), using `label` to
+    /// format the elements of the sequences.
+    pub(crate) fn format_graph_with(
+        &self,
+        w: &mut impl Write,
+        label: impl Fn(&T) -> String,
+    ) -> Result<()> {
+        self.render(w, &label, &RenderOptions::default())
+    }
+
+    /// Writes the graph with the `options`, `label` formatting the
+    /// elements, also used for the [`Format::Dot`](super::Format::Dot)
+    /// export
+    pub(super) fn render(
+        &self,
+        w: &mut impl Write,
+        label: &dyn Fn(&T) -> String,
+        options: &RenderOptions<'_, T>,
+    ) -> Result<()> {
+        let marks = self.marks(&options.highlight);
+        // Labels of the elements and of the suffixes, escaped
+        let element = |e: &T| escape_dot(&label(e));
+        let suffix = |s: &[T]| escape_dot(&suffix_label(s, label, options.max_suffix));
+
         writeln!(w, "digraph xMCS {{")?;
-        writeln!(w, "\trankdir = LR;")?;
+        writeln!(w, "\trankdir = {};", options.rank_dir.as_str())?;
         writeln!(w, "\tpad = 1;")?;
         writeln!(w, "\tnewrank = yes;")?;
 
         writeln!(w, "\tstart [shape = none, height = 0, width = 0];")?;
 
-        writeln!(w, "\tsubgraph cluster_info {{")?;
-        writeln!(w, "\t\trank = same;")?;
-        writeln!(w, "\t\tnode [shape = box];")?;
-        writeln!(
-            w,
-            r#"{}min_len [label = "Minimum subsequence length: {}"];"#,
-            "\t\t", self.len
-        )?;
-        writeln!(
-            w,
-            r#"{}states [label = "{} states"];"#,
-            "\t\t",
-            self.nodes.len()
-        )?;
-        writeln!(
-            w,
-            r#"{}opt [label = "Merge optimisations enabled"];"#,
-            "\t\t",
-        )?;
-        //writeln!(w, "\t\tmin_len -> states -> opt -> start [style = invis];")?;
-        writeln!(w, "\t}}")?;
-
-        self.write_clusters(w)?;
-
-        self.write_edges(w)?;
+        if options.info {
+            writeln!(w, "\tsubgraph cluster_info {{")?;
+            writeln!(w, "\t\trank = same;")?;
+            writeln!(w, "\t\tnode [shape = box];")?;
+            writeln!(
+                w,
+                r#"{}min_len [label = "Minimum subsequence length: {}"];"#,
+                "\t\t", self.len
+            )?;
+            let stats = self.stats();
+            writeln!(
+                w,
+                r#"{}states [label = "{} states ({} reachable)"];"#,
+                "\t\t", stats.nodes, stats.reachable
+            )?;
+            writeln!(
+                w,
+                r#"{}types [label = "{} splits, {} elements, {} ends ({} distinct slices)"];"#,
+                "\t\t", stats.split, stats.element, stats.end, stats.distinct_suffix_slices
+            )?;
+            writeln!(
+                w,
+                r#"{}max_len [label = "Maximum subsequence length: {}"];"#,
+                "\t\t", stats.depth
+            )?;
+            writeln!(w, "\t}}")?;
+        }
+
+        self.write_clusters(w, suffix, &marks)?;
+
+        self.write_edges(w, element, &marks)?;
 
         writeln!(w, "}}")
     }
 
-    fn write_clusters(&self, w: &mut impl Write) -> Result<()> {
-        let depths = self.compute_depths();
+    /// Style of the nodes and edges given by `highlight`
+    fn marks(&self, highlight: &Highlight) -> Marks {
+        let nodes = highlight.path.iter().copied().collect();
+        let edges = highlight
+            .path
+            .windows(2)
+            .map(|pair| (pair[0], pair[1]))
+            .collect();
+
+        let dimmed = if highlight.dim_short {
+            self.short_nodes()
+        } else {
+            Vec::new()
+        };
+
+        Marks {
+            nodes,
+            edges,
+            dimmed,
+        }
+    }
 
-        /* write!(w, "\t")?;
-        for (depth, _) in depths.iter().enumerate().skip(1).rev() {
-            write!(w, "depth_{} -> ", depth)?;
+    /// Whether each node is not on a path of the maximum length
+    ///
+    /// As [`levels`](Dag::levels), relies on the builders pushing the
+    /// children of a node before it.
+    fn short_nodes(&self) -> Vec<bool> {
+        // Length of the longest prefix leading to each node. The children
+        // come before their parents, so the nodes are visited after all
+        // their parents.
+        let mut prefix = vec![None; self.nodes.len()];
+        prefix[self.start] = Some(0);
+        for i in (0..=self.start).rev() {
+            let Some(p) = prefix[i] else { continue };
+            let mut update = |child: usize, length: usize| {
+                prefix[child] = max(prefix[child], Some(length));
+            };
+            match self.nodes.get(i).inner {
+                NodeType::Element { child, .. } => update(child, p + 1),
+                NodeType::Split { child1, child2 } => {
+                    update(child1, p);
+                    update(child2, p);
+                }
+                _ => (),
+            }
         }
-        writeln!(w, "depth_0;")?; */
+
+        let longest = self.nodes.get(self.start).max_length;
+        self.nodes
+            .iter()
+            .zip(prefix)
+            .map(|(node, p)| p.is_none_or(|p| p + node.max_length < longest))
+            .collect()
+    }
+
+    fn write_clusters(
+        &self,
+        w: &mut impl Write,
+        suffix: impl Fn(&[T]) -> String,
+        marks: &Marks,
+    ) -> Result<()> {
+        let depths = self.levels();
 
         for (depth, nodes) in depths.iter().enumerate() {
-            /* writeln!(w, "\t\trank = same;")?;
-            writeln!(w, "\t\tnode_{};", nodes[0])?;
-            writeln!(w, "\t\tdepth_{};", depth)?;
-            writeln!(w, "\t}}")?; */
             writeln!(w, r#"{}node [shape = point, label = ""];"#, "\t")?;
 
             if depth == 0 {
                 writeln!(w, "\t{{")?;
                 writeln!(w, "\t\tnode [shape = none, fontcolor = green];")?;
                 for &idx in nodes {
-                    let node = &self.nodes[idx];
-                    match node.inner {
-                        NodeType::End { suffix } => {
-                            write!(w, "\t\t")?;
-                            write_seq(w, suffix)?;
-                            write!(w, r#" [label = ""#)?;
-                            write_seq(w, suffix)?;
-                            writeln!(w, r#""];"#)?;
+                    let node = self.nodes.get(idx);
+                    if let NodeType::End { suffix: s } = node.inner {
+                        write!(w, r#"{}node_{} [label = "{}""#, "\t\t", idx, suffix(s))?;
+                        if let Some(style) = marks.node(idx, "fontcolor") {
+                            write!(w, ", {style}")?;
                         }
-                        _ => (),
+                        writeln!(w, "];")?;
                     }
                 }
                 writeln!(w, "\t}}")?;
             } else {
                 for &idx in nodes {
-                    writeln!(w, "\tnode_{};", idx)?;
-                }
-            }
-
-            /*
-            if depth == 0 {
-                writeln!(w, "\tsubgraph cluster_subseq {{")?;
-                writeln!(w, "\t\trank = sink;")?;
-                writeln!(w, r#"{}label = "Subsequences";"#, "\t\t")?;
-                for &idx in nodes {
-                    let node = &self.nodes[idx];
-                    write!(w, "\t\t")?;
-                    match node.inner {
-                        NodeType::End { suffix } => {
-                            write_seq(w, suffix)?;
-                            write!(w, r#" [label = ""#)?;
-                            write_seq(w, suffix)?;
-                            write!(w, r#"", shape = none, fontcolor = green]"#)?;
-                        }
-                        _ => (),
+                    match marks.node(idx, "color") {
+                        Some(style) => writeln!(w, "\tnode_{idx} [{style}];")?,
+                        None => writeln!(w, "\tnode_{idx};")?,
                     }
-                    writeln!(w, ";")?;
                 }
-                writeln!(w, "\t}}")?;
-            } else if depth > 1 {
-                writeln!(w, "\t{{")?;
-                writeln!(w, "\t\tlabelloc = b;")?;
-                writeln!(w, "\t\tstyle = dashed;")?;
-                writeln!(w, r#"{}label = "len = {}";"#, "\t\t", depth - 1)?;
-                for &idx in nodes {
-                    let node = &self.nodes[idx];
-                    match node.inner {
-                        NodeType::Element {..} => writeln!(w, "\t\tnode_{}", idx)?,
-                        _ => ()
-                    }
-                }
-                writeln!(w, "\t}}")?;
-            } */
+            }
         }
 
         Ok(())
     }
 
-    fn write_edges(&self, w: &mut impl Write) -> Result<()> {
+    fn write_edges(
+        &self,
+        w: &mut impl Write,
+        element: impl Fn(&T) -> String,
+        marks: &Marks,
+    ) -> Result<()> {
         writeln!(w, "\tstart -> node_{} [dir = back, arrowhead = none, arrowtail = crow, arrowsize = 2, color = green];", self.start)?;
 
         for (i, node) in self.nodes.iter().enumerate() {
-            match &node.inner {
+            match node.inner {
                 NodeType::Element { child, value } => {
-                    write!(w, "\tnode_{} -> ", i)?;
-                    if let NodeType::End { suffix } = self.nodes[*child].inner {
-                        write_seq(w, suffix)?;
-                        write!(w, " [arrowhead = dot, ")?;
-                    } else {
-                        write!(w, "node_{} [", child)?;
+                    write!(w, "\tnode_{i} -> node_{child} [")?;
+                    if let NodeType::End { .. } = self.nodes.get(child).inner {
+                        write!(w, "arrowhead = dot, ")?;
                     }
                     writeln!(
                         w,
-                        "label = {}, weight = 2, color = blue, fontcolor = red];",
-                        value
+                        r#"label = "{}", weight = 2, {}, fontcolor = red];"#,
+                        element(&value),
+                        marks.edge(i, child).unwrap_or("color = blue")
                     )?;
                 }
                 NodeType::Split { child1, child2 } => {
-                    write!(w, "\tnode_{} -> ", i)?;
-                    if let NodeType::End { suffix } = self.nodes[*child1].inner {
-                        write_seq(w, suffix)?;
-                        write!(w, " [arrowhead = dot]")?;
-                    } else {
-                        write!(w, "node_{}", child1)?;
-                    }
-                    writeln!(w, ";")?;
-
-                    write!(w, "\tnode_{} -> ", i)?;
-                    if let NodeType::End { suffix } = self.nodes[*child2].inner {
-                        write_seq(w, suffix)?;
-                        write!(w, " [arrowhead = dot]")?;
-                    } else {
-                        write!(w, "node_{}", child2)?;
+                    for child in [child1, child2] {
+                        write!(w, "\tnode_{i} -> node_{child}")?;
+                        let mut attributes = Vec::new();
+                        if let NodeType::End { .. } = self.nodes.get(child).inner {
+                            attributes.push("arrowhead = dot");
+                        }
+                        attributes.extend(marks.edge(i, child));
+                        if !attributes.is_empty() {
+                            write!(w, " [{}]", attributes.join(", "))?;
+                        }
+                        writeln!(w, ";")?;
                     }
-                    writeln!(w, ";")?;
                 }
                 _ => (),
             }
@@ -180,50 +400,257 @@ where
 
         Ok(())
     }
+}
 
-    fn compute_depths(&self) -> Vec<Vec<usize>> {
-        use priority_queue::PriorityQueue;
-
-        let mut queue = PriorityQueue::with_capacity(self.nodes.len());
-        queue.push(self.start, self.len);
+/// Label of a suffix, shortened to its first `max` elements if it is
+/// longer
+fn suffix_label<T>(suffix: &[T], label: impl Fn(&T) -> String, max: Option<usize>) -> String {
+    match max {
+        Some(max) if suffix.len() > max => {
+            let head = suffix[..max].iter().map(label).collect::<String>();
+            format!("{}… (+{})", head, suffix.len() - max)
+        }
+        _ => suffix.iter().map(label).collect(),
+    }
+}
 
-        let mut res = vec![vec![]; self.len + 2];
-        let mut visited = vec![false; self.nodes.len()];
+/// Escapes `s` to be written between the quotes of a DOT string
+fn escape_dot(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            c => res.push(c),
+        }
+    }
+    res
+}
 
-        while let Some((idx, depth)) = queue.pop() {
-            let node = &self.nodes[idx];
-            visited[idx] = true;
+// === Tests ===
 
-            if let NodeType::End { .. } = node.inner {
-                res[0].push(idx);
-            } else {
-                res[depth + 1].push(idx);
-            }
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dag::{Node, Nodes};
 
-            match node.inner {
-                NodeType::Split { child1, child2 } => {
-                    if !visited[child1] {
-                        queue.push_increase(child1, depth);
-                    }
-                    if !visited[child2] {
-                        queue.push_increase(child2, depth);
-                    }
-                }
-                NodeType::Element { child, .. } => {
-                    queue.push_increase(child, depth.saturating_sub(1));
-                }
-                _ => (),
-            }
+    fn node<T>(length: usize, inner: NodeType<'_, T>) -> Node<'_, T> {
+        Node {
+            max_length: length,
+            min_length: length,
+            inner,
         }
+    }
+
+    /// Graph of `{"ABC", "B", "C"}`, whose nodes are
+    /// `5 -> (2 -> (0: "C", 1: "B"), 4 -A-> 3: "BC")`
+    fn graph(seq: &[u8]) -> Dag<'_, u8> {
+        let mut nodes = Nodes::default();
+        nodes.push(node(1, NodeType::End { suffix: &seq[2..] }));
+        nodes.push(node(1, NodeType::End { suffix: &seq[1..2] }));
+        nodes.push(node(
+            1,
+            NodeType::Split {
+                child1: 0,
+                child2: 1,
+            },
+        ));
+        nodes.push(node(2, NodeType::End { suffix: &seq[1..] }));
+        nodes.push(node(
+            3,
+            NodeType::Element {
+                value: b'A',
+                child: 3,
+            },
+        ));
+        let mut start = node(
+            3,
+            NodeType::Split {
+                child1: 2,
+                child2: 4,
+            },
+        );
+        start.min_length = 1;
+        nodes.push(start);
+        Dag::from_raw(nodes, Some(5), 1, 0)
+    }
+
+    fn render(dag: &Dag<'_, u8>, options: &RenderOptions<'_, u8>) -> String {
+        let mut res = Vec::new();
+        dag.format_graph_with_options(&mut res, options).unwrap();
+        String::from_utf8(res).unwrap()
+    }
+
+    /// Formats the bytes as characters
+    fn letters() -> Formatter<'static, u8> {
+        Box::new(|&e| char::from(e).to_string())
+    }
 
+    /// Lines of the nodes and the edges, sorted
+    fn node_lines(dot: &str) -> Vec<&str> {
+        let mut res = dot
+            .lines()
+            .map(str::trim)
+            .filter(|l| l.starts_with("node_"))
+            .collect::<Vec<_>>();
+        res.sort_unstable();
         res
     }
-}
 
-fn write_seq(w: &mut impl Write, seq: &[impl Display]) -> Result<()> {
-    for e in seq {
-        write!(w, "{}", e)?;
+    #[test]
+    fn test_short_nodes() {
+        let dag = graph(b"ABC");
+        assert_eq!(
+            vec![true, true, true, false, false, false],
+            dag.short_nodes()
+        );
+    }
+
+    #[test]
+    fn test_highlight() {
+        let dag = graph(b"ABC");
+        let options = RenderOptions {
+            label: Some(letters()),
+            highlight: Highlight {
+                path: dag.path_of(b"ABC").unwrap(),
+                dim_short: true,
+            },
+            ..RenderOptions::default()
+        };
+        assert_eq!(vec![5, 4, 3], options.highlight.path);
+        assert_eq!(
+            vec![
+                r#"node_0 [label = "C", fontcolor = grey];"#,
+                r#"node_1 [label = "B", fontcolor = grey];"#,
+                "node_2 -> node_0 [arrowhead = dot, color = grey];",
+                "node_2 -> node_1 [arrowhead = dot, color = grey];",
+                "node_2 [color = grey];",
+                r#"node_3 [label = "BC", fontcolor = red];"#,
+                r#"node_4 -> node_3 [arrowhead = dot, label = "A", weight = 2, color = red, penwidth = 3, fontcolor = red];"#,
+                "node_4 [color = red];",
+                "node_5 -> node_2 [color = grey];",
+                "node_5 -> node_4 [color = red, penwidth = 3];",
+                "node_5 [color = red];",
+            ],
+            node_lines(&render(&dag, &options))
+        );
+
+        // Path of a shorter subsequence, without greying out
+        let options = RenderOptions {
+            label: Some(letters()),
+            highlight: Highlight {
+                path: dag.path_of(b"B").unwrap(),
+                dim_short: false,
+            },
+            ..RenderOptions::default()
+        };
+        assert_eq!(vec![5, 2, 1], options.highlight.path);
+        assert_eq!(
+            vec![
+                r#"node_0 [label = "C"];"#,
+                r#"node_1 [label = "B", fontcolor = red];"#,
+                "node_2 -> node_0 [arrowhead = dot];",
+                "node_2 -> node_1 [arrowhead = dot, color = red, penwidth = 3];",
+                "node_2 [color = red];",
+                r#"node_3 [label = "BC"];"#,
+                r#"node_4 -> node_3 [arrowhead = dot, label = "A", weight = 2, color = blue, fontcolor = red];"#,
+                "node_4;",
+                "node_5 -> node_2 [color = red, penwidth = 3];",
+                "node_5 -> node_4;",
+                "node_5 [color = red];",
+            ],
+            node_lines(&render(&dag, &options))
+        );
+    }
+
+    #[test]
+    fn test_labels() {
+        let seq = br#"A"B\CD"#;
+        let dag = Dag::singleton(2, seq);
+
+        // The elements are formatted with `Display` by default
+        let dot = render(&dag, &RenderOptions::default());
+        assert_eq!(
+            vec![r#"node_0 [label = "653466926768"];"#],
+            node_lines(&dot)
+        );
+
+        // Quotes and backslashes are escaped
+        let options = RenderOptions {
+            label: Some(letters()),
+            ..RenderOptions::default()
+        };
+        let dot = render(&dag, &options);
+        assert_eq!(vec![r#"node_0 [label = "A\"B\\CD"];"#], node_lines(&dot));
+
+        // Long suffixes are shortened
+        let options = RenderOptions {
+            label: Some(letters()),
+            max_suffix: Some(3),
+            ..RenderOptions::default()
+        };
+        let dot = render(&dag, &options);
+        assert_eq!(vec![r#"node_0 [label = "A\"B… (+3)"];"#], node_lines(&dot));
+        let options = RenderOptions {
+            label: Some(letters()),
+            max_suffix: Some(6),
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            dot.replace("A\\\"B… (+3)", "A\\\"B\\\\CD"),
+            render(&dag, &options)
+        );
+
+        // Elements on the edges
+        let mut nodes = Nodes::default();
+        nodes.push(node(1, NodeType::End { suffix: &seq[3..4] }));
+        nodes.push(node(
+            2,
+            NodeType::Element {
+                value: b'"',
+                child: 0,
+            },
+        ));
+        let dag = Dag::from_raw(nodes, Some(1), 2, 0);
+        let options = RenderOptions {
+            label: Some(letters()),
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            vec![
+                r#"node_0 [label = "\\"];"#,
+                r#"node_1 -> node_0 [arrowhead = dot, label = "\"", weight = 2, color = blue, fontcolor = red];"#,
+                "node_1;",
+            ],
+            node_lines(&render(&dag, &options))
+        );
     }
 
-    Ok(())
+    #[test]
+    fn test_info() {
+        let dag = graph(b"ABC");
+
+        let dot = render(&dag, &RenderOptions::default());
+        let info = [
+            r#"min_len [label = "Minimum subsequence length: 1"];"#,
+            r#"states [label = "6 states (6 reachable)"];"#,
+            r#"types [label = "2 splits, 1 elements, 3 ends (3 distinct slices)"];"#,
+            r#"max_len [label = "Maximum subsequence length: 3"];"#,
+        ];
+        assert!(dot.contains("subgraph cluster_info {"));
+        for line in &info {
+            assert!(dot.lines().any(|l| l.trim() == *line), "{}", line);
+        }
+
+        let options = RenderOptions {
+            info: false,
+            rank_dir: RankDir::BottomTop,
+            ..RenderOptions::default()
+        };
+        let dot = render(&dag, &options);
+        assert!(!dot.contains("cluster_info"));
+        assert!(!dot.contains("min_len"));
+        assert!(dot.contains("rankdir = BT;"));
+    }
 }
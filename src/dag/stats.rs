@@ -0,0 +1,189 @@
+//! Statistics on the shape of a graph
+
+use super::{Dag, NodeType};
+
+use std::collections::HashSet;
+
+/// Statistics on a graph, returned by [`Dag::stats`]
+///
+/// The counts by type of node cover all the nodes of the graph, the
+/// other values only the nodes reachable from its first node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stats {
+    /// Number of nodes used to represent the graph
+    pub nodes: usize,
+    /// Number of nodes reachable from the first node
+    pub reachable: usize,
+    /// Number of Empty nodes
+    pub empty: usize,
+    /// Number of End nodes
+    pub end: usize,
+    /// Number of Split nodes
+    pub split: usize,
+    /// Number of Element nodes
+    pub element: usize,
+    /// Number of distinct slices of the sequences among the suffixes of
+    /// the reachable End nodes: the suffixes are compared by address and
+    /// length, so equal suffixes at different places of the sequences
+    /// are counted separately
+    pub distinct_suffix_slices: usize,
+    /// Length of the longest sequence
+    pub depth: usize,
+    /// Number of reachable nodes at each level, as laid out by
+    /// [`format_graph`](Dag::format_graph): the End nodes first, then the
+    /// other nodes by the number of elements left after them
+    pub widths: Vec<usize>,
+    /// Fraction of the nodes inserted during the construction that were
+    /// shared with an existing node
+    pub sharing: f64,
+    /// Estimated number of bytes allocated
+    pub bytes: usize,
+}
+
+impl<T> Dag<'_, T>
+where
+    T: Copy,
+{
+    /// Statistics on the nodes of the graph
+    ///
+    /// # Examples
+    /// ```
+    /// let dag = xmcs::dag::xmcs2(3, b"ABCD", b"ACBD");
+    ///
+    /// let stats = dag.stats();
+    /// assert_eq!(4, stats.reachable);
+    /// assert_eq!(2, stats.end);
+    /// assert_eq!(3, stats.depth);
+    /// ```
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats {
+            nodes: self.nodes.len(),
+            reachable: 0,
+            empty: 0,
+            end: 0,
+            split: 0,
+            element: 0,
+            distinct_suffix_slices: 0,
+            depth: self.nodes.get(self.start).max_length,
+            widths: Vec::new(),
+            sharing: 0.,
+            bytes: self.nodes.bytes(),
+        };
+
+        for node in self.nodes.iter() {
+            match node.inner {
+                NodeType::Empty => stats.empty += 1,
+                NodeType::End { .. } => stats.end += 1,
+                NodeType::Split { .. } => stats.split += 1,
+                NodeType::Element { .. } => stats.element += 1,
+            }
+        }
+
+        let levels = self.levels();
+        stats.widths = levels.iter().map(Vec::len).collect();
+        stats.reachable = stats.widths.iter().sum();
+        stats.distinct_suffix_slices = levels[0]
+            .iter()
+            .filter_map(|&idx| match self.nodes.get(idx).inner {
+                // Compared by address, as when the nodes are shared
+                NodeType::End { suffix: [] } => Some((0, 0)),
+                NodeType::End { suffix } => Some((suffix.as_ptr() as usize, suffix.len())),
+                _ => None,
+            })
+            .collect::<HashSet<_>>()
+            .len();
+
+        // A graph always has at least one node
+        let inserted = self.nodes.len() + self.shared;
+        #[allow(clippy::cast_precision_loss)]
+        let sharing = self.shared as f64 / inserted as f64;
+        stats.sharing = sharing;
+
+        stats
+    }
+}
+
+impl<T> Dag<'_, T>
+where
+    T: Copy,
+{
+    /// Indices of the reachable nodes by level: the End nodes first, then
+    /// the other nodes by the largest number of elements left after them,
+    /// counted down from `len` on the first node
+    ///
+    /// The builders push the children of a node before it, so that the
+    /// nodes can be visited by decreasing index, each after all of its
+    /// parents.
+    pub(super) fn levels(&self) -> Vec<Vec<usize>> {
+        let mut res = vec![vec![]; self.len + 2];
+        let mut depths = vec![None; self.start + 1];
+        depths[self.start] = Some(self.len);
+
+        // Children have smaller indices than their parents, so each node
+        // has its final depth when it is reached
+        for idx in (0..=self.start).rev() {
+            let Some(depth) = depths[idx] else {
+                continue;
+            };
+            let mut visit = |child: usize, depth: usize| {
+                depths[child] = depths[child].max(Some(depth));
+            };
+
+            match self.nodes.get(idx).inner {
+                NodeType::End { .. } => res[0].push(idx),
+                NodeType::Empty => res[depth + 1].push(idx),
+                NodeType::Split { child1, child2 } => {
+                    res[depth + 1].push(idx);
+                    visit(child1, depth);
+                    visit(child2, depth);
+                }
+                NodeType::Element { child, .. } => {
+                    res[depth + 1].push(idx);
+                    visit(child, depth.saturating_sub(1));
+                }
+            }
+        }
+
+        res
+    }
+}
+
+// === Tests ===
+
+#[cfg(test)]
+mod test {
+    use crate::dag::{xmcs2, Dag};
+
+    #[test]
+    fn test_stats() {
+        let dag = xmcs2(3, b"ADBCBAD", b"ADCBACD");
+        let stats = dag.stats();
+
+        assert_eq!(dag.node_count(), stats.nodes);
+        assert_eq!(
+            stats.nodes,
+            stats.empty + stats.end + stats.split + stats.element
+        );
+        assert!(stats.reachable <= stats.nodes);
+        assert!(stats.distinct_suffix_slices <= stats.end);
+        assert_eq!(6, stats.depth);
+        assert_eq!(dag.len + 2, stats.widths.len());
+        assert!(stats.bytes > 0);
+        // One of the 14 nodes inserted was shared
+        assert_eq!(1, dag.shared_count());
+        assert!((stats.sharing - 1. / 14.).abs() < f64::EPSILON);
+
+        // Every reachable node is in exactly one level
+        let mut levels = dag.levels().concat();
+        levels.sort_unstable();
+        levels.dedup();
+        assert_eq!(stats.reachable, levels.len());
+
+        let empty = Dag::<u8>::empty(3).stats();
+        assert_eq!(1, empty.nodes);
+        assert_eq!(1, empty.empty);
+        assert_eq!(1, empty.reachable);
+        assert_eq!(0, empty.depth);
+        assert!(empty.sharing.abs() < f64::EPSILON);
+    }
+}
@@ -0,0 +1,176 @@
+//! Subsequence tables shared between the End nodes of a graph
+//!
+//! Each End node reached while adding a sequence runs the algorithm for
+//! two sequences on its suffix and a tail of the sequence, which needs a
+//! [`SubSeq`] table. The suffixes are tails of the same few input
+//! sequences, and the tails of the sequence all end at the same place,
+//! so a table built for longer tails ending at the same addresses also
+//! answers the queries on the shorter ones: it is indexed from the end.
+//!
+//! The tables are cached by the address of the end of the suffix. A
+//! cached table is reused if its tails are long enough and its band
+//! wide enough, and is otherwise replaced by a table covering both the
+//! old and the new queries, unless it would be much larger than a table
+//! built for the new query alone.
+
+use super::memo::{map_bytes, PositionHasher};
+
+use std::cmp::max;
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+use std::rc::Rc;
+
+use crate::substr::SubString as SubSeq;
+
+type Map<K, V> = HashMap<K, V, BuildHasherDefault<PositionHasher>>;
+
+/// Maximum ratio between the area of a merged table and the area of a
+/// table built for a single query
+const MAX_GROWTH: usize = 4;
+
+/// Table built for two tails of the sequences
+struct Table<'a, T> {
+    /// Tail of the sequences of the End nodes
+    s1: &'a [T],
+    /// Tail of the sequence being added
+    s2: &'a [T],
+    /// Width of the band of the table
+    delta: usize,
+    substr: Rc<SubSeq>,
+}
+
+/// Cache of the subsequence tables, by the end of the suffixes
+pub(super) struct Tables<'a, T> {
+    tables: Map<usize, Table<'a, T>>,
+}
+
+impl<T> Default for Tables<'_, T> {
+    fn default() -> Self {
+        Self {
+            tables: Map::default(),
+        }
+    }
+}
+
+impl<'a, T> Tables<'a, T>
+where
+    T: Eq,
+{
+    /// Returns a table answering the queries of the algorithm for two
+    /// sequences on `s1` and `s2` with minimum length `len`
+    ///
+    /// `s2` must always be a tail of the same sequence, and
+    /// `len <= min(|s1|, |s2|)`.
+    pub(super) fn get(&mut self, len: usize, s1: &'a [T], s2: &'a [T]) -> Rc<SubSeq> {
+        let (p, q) = (s1.len(), s2.len());
+        let fresh = max(p, q) - len;
+        // Empty suffixes all end at the same address
+        let key = if s1.is_empty() {
+            0
+        } else {
+            s1.as_ptr_range().end as usize
+        };
+
+        if let Some(old) = self.tables.get(&key) {
+            let (l1, l2) = (old.s1.len(), old.s2.len());
+            if l1 >= p && l2 >= q && (l1 - p).abs_diff(l2 - q) + fresh <= old.delta {
+                return Rc::clone(&old.substr);
+            }
+        }
+
+        // Longest tails, and a band covering the queries of both
+        let (t1, t2, delta) = self.tables.get(&key).map_or((s1, s2, fresh), |old| {
+            let t1 = if old.s1.len() > p { old.s1 } else { s1 };
+            let t2 = if old.s2.len() > q { old.s2 } else { s2 };
+            let (ext1, ext2) = (t1.len() - old.s1.len(), t2.len() - old.s2.len());
+            let needed = (t1.len() - p).abs_diff(t2.len() - q) + fresh;
+            let delta =
+                max(needed, old.delta + ext1.abs_diff(ext2)).max(t1.len().abs_diff(t2.len()));
+
+            if area(t1, t2, delta) > MAX_GROWTH * area(s1, s2, fresh) {
+                (s1, s2, fresh)
+            } else {
+                (t1, t2, delta)
+            }
+        });
+
+        let table = Rc::new(SubSeq::new(t1, t2, delta));
+        self.tables.insert(
+            key,
+            Table {
+                s1: t1,
+                s2: t2,
+                delta,
+                substr: Rc::clone(&table),
+            },
+        );
+        table
+    }
+}
+
+impl<T> Tables<'_, T> {
+    /// Estimated number of bytes allocated
+    pub(super) fn bytes(&self) -> usize {
+        map_bytes(&self.tables)
+            + self
+                .tables
+                .values()
+                .map(|t| t.substr.memory_usage())
+                .sum::<usize>()
+    }
+}
+
+/// Number of cells of a table built for `s1` and `s2` with a band of
+/// width `delta`
+fn area<T>(s1: &[T], s2: &[T], delta: usize) -> usize {
+    (max(s1.len(), s2.len()) + 1) * (2 * delta + 1)
+}
+
+// === Tests ===
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tables() {
+        let s1 = b"ABCABDCABD";
+        let s2 = b"BACDABCBADC";
+        let mut tables = Tables::default();
+
+        // Queries of the algorithm for two sequences: answered for all
+        // the tails at least `len` long
+        let check = |table: &SubSeq, len: usize, t1: &[u8], t2: &[u8]| {
+            let reference = SubSeq::new(t1, t2, max(t1.len(), t2.len()) - len);
+            for l1 in len..=t1.len() {
+                for l2 in len..=t2.len() {
+                    assert_eq!(
+                        reference.is_substring_from_end(l1, l2),
+                        table.is_substring_from_end(l1, l2),
+                    );
+                }
+            }
+        };
+
+        let first = tables.get(4, &s1[4..], &s2[5..]);
+        check(&first, 4, &s1[4..], &s2[5..]);
+
+        // Shorter tails in the band: reused
+        let table = tables.get(4, &s1[5..], &s2[6..]);
+        check(&table, 4, &s1[5..], &s2[6..]);
+        assert!(Rc::ptr_eq(&first, &table));
+
+        // Longer tails: merged, and still answers the first queries
+        let merged = tables.get(3, &s1[..], &s2[2..]);
+        check(&merged, 3, &s1[..], &s2[2..]);
+        assert!(!Rc::ptr_eq(&first, &merged));
+        let table = tables.get(4, &s1[4..], &s2[5..]);
+        check(&table, 4, &s1[4..], &s2[5..]);
+        assert!(Rc::ptr_eq(&merged, &table));
+
+        // Another end
+        let table = tables.get(2, &s1[..5], &s2[8..]);
+        check(&table, 2, &s1[..5], &s2[8..]);
+        assert!(!Rc::ptr_eq(&merged, &table));
+    }
+}
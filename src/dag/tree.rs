@@ -0,0 +1,241 @@
+//! Text rendering of the graphs, used to debug them without graphviz
+//!
+//! The graph is printed as a tree with one node per line, starting with
+//! its index. The child of an Element node follows it at the same
+//! indentation, the two children of a Split node are indented below it,
+//! and a node already printed is only referred to by its index.
+
+use super::{Dag, NodeType};
+
+use std::fmt::{self, Debug, Write};
+
+/// Maximum depth of the tree printed by the [`Debug`] implementation
+const DEBUG_DEPTH: usize = 16;
+/// Maximum width of the lines printed by the [`Debug`] implementation
+const DEBUG_WIDTH: usize = 100;
+/// Maximum number of lines printed by the [`Debug`] implementation
+const DEBUG_LINES: usize = 200;
+
+/// Line left to print
+struct Line {
+    index: usize,
+    /// Start of the line
+    prefix: String,
+    /// Start of the lines of the children of a Split node
+    nested: String,
+    /// Number of Split nodes above
+    depth: usize,
+}
+
+impl<T> Dag<'_, T>
+where
+    T: Debug + Copy,
+{
+    /// Writes the graph into `w` as an indented tree
+    ///
+    /// The Split nodes deeper than `max_depth` are not expanded, and the
+    /// lines longer than `max_width` characters are shortened. Each line
+    /// starts with the index of its node (`#index`), and the nodes
+    /// already printed are replaced by a reference to their index
+    /// (`@index`). After `max_lines` lines, the rest of the tree is
+    /// replaced by the number of reachable nodes left
+    /// (`… (n more nodes)`).
+    ///
+    /// # Errors
+    /// Forwards errors from writing into `w`.
+    ///
+    /// # Examples
+    /// ```
+    /// # use xmcs::dag::xmcs2;
+    /// let dag = xmcs2(3, b"ABCD", b"ACBD");
+    ///
+    /// let mut tree = String::new();
+    /// dag.format_tree(&mut tree, 8, 80, 10).unwrap();
+    /// assert_eq!(
+    ///     "#3 65\n#2 split\n├─ #0 end [67, 68]\n└─ #1 end [66, 68]\n",
+    ///     tree
+    /// );
+    ///
+    /// let mut tree = String::new();
+    /// dag.format_tree(&mut tree, 8, 80, 2).unwrap();
+    /// assert_eq!("#3 65\n#2 split\n… (2 more nodes)\n", tree);
+    /// ```
+    pub fn format_tree(
+        &self,
+        w: &mut impl Write,
+        max_depth: usize,
+        max_width: usize,
+        max_lines: usize,
+    ) -> fmt::Result {
+        let mut printed = vec![false; self.nodes.len()];
+        let mut stack = vec![Line {
+            index: self.start,
+            prefix: String::new(),
+            nested: String::new(),
+            depth: 0,
+        }];
+
+        for _ in 0..max_lines {
+            let Some(line) = stack.pop() else {
+                return Ok(());
+            };
+            let Line {
+                index,
+                prefix,
+                nested,
+                depth,
+            } = line;
+
+            let content = if std::mem::replace(&mut printed[index], true) {
+                format!("@{index}")
+            } else {
+                match self.nodes.get(index).inner {
+                    NodeType::Empty => format!("#{index} empty"),
+                    NodeType::End { suffix } => format!("#{index} end {suffix:?}"),
+                    NodeType::Element { value, child } => {
+                        stack.push(Line {
+                            index: child,
+                            prefix: nested.clone(),
+                            nested: nested.clone(),
+                            depth,
+                        });
+                        format!("#{index} {value:?}")
+                    }
+                    NodeType::Split { .. } if depth >= max_depth => {
+                        // Expanded again if it is reached at a lower depth
+                        printed[index] = false;
+                        format!("#{index} split …")
+                    }
+                    NodeType::Split { child1, child2 } => {
+                        stack.push(Line {
+                            index: child2,
+                            prefix: format!("{nested}└─ "),
+                            nested: format!("{nested}   "),
+                            depth: depth + 1,
+                        });
+                        stack.push(Line {
+                            index: child1,
+                            prefix: format!("{nested}├─ "),
+                            nested: format!("{nested}│  "),
+                            depth: depth + 1,
+                        });
+                        format!("#{index} split")
+                    }
+                }
+            };
+
+            let mut line = prefix + &content;
+            if let Some((cut, _)) = line.char_indices().nth(max_width.saturating_sub(1)) {
+                if line[cut..].chars().nth(1).is_some() {
+                    line.truncate(cut);
+                    line.push('…');
+                }
+            }
+            writeln!(w, "{line}")?;
+        }
+
+        if !stack.is_empty() {
+            let reachable = self.levels().iter().map(Vec::len).sum::<usize>();
+            let left = reachable - printed.iter().filter(|&&p| p).count();
+            writeln!(w, "… ({left} more nodes)")?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> Debug for Dag<'_, T>
+where
+    T: Debug + Copy,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Dag {{ len: {}, nodes: {} }}",
+            self.len,
+            self.nodes.len()
+        )?;
+        self.format_tree(f, DEBUG_DEPTH, DEBUG_WIDTH, DEBUG_LINES)
+    }
+}
+
+// === Tests ===
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dag::xmcsk;
+    use crate::reference::random_sequences;
+
+    fn tree(dag: &Dag<'_, char>, max_depth: usize, max_width: usize) -> String {
+        let mut res = String::new();
+        dag.format_tree(&mut res, max_depth, max_width, usize::MAX)
+            .unwrap();
+        res
+    }
+
+    #[test]
+    fn test_format_tree() {
+        let seqs = ["ADBCBAD", "ADCBACD", "ABDCABDA"]
+            .iter()
+            .map(|s| s.chars().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let seqs = seqs.iter().map(|s| &s[..]).collect::<Vec<_>>();
+        let dag = xmcsk(4, &seqs);
+
+        let full = tree(&dag, usize::MAX, usize::MAX);
+        // Every node reachable is printed once, the others are referred to
+        let lines = full.lines().collect::<Vec<_>>();
+        let printed = lines.iter().filter(|l| l.contains('#')).count();
+        assert!(printed <= dag.node_count());
+        assert!(lines.iter().any(|l| l.contains('@')));
+        assert!(full.contains("end ['D']"));
+
+        // No Split node is expanded at depth 0
+        let NodeType::Element { child, .. } = dag.nodes.get(dag.start).inner else {
+            panic!("{:?}", dag);
+        };
+        assert_eq!(
+            format!("#{} 'A'\n#{} split …\n", dag.start, child),
+            tree(&dag, 0, 80)
+        );
+        let cut = format!("#{child} spl");
+        assert_eq!(
+            format!("#{} 'A'\n{}…\n", dag.start, cut),
+            tree(&dag, 0, cut.len() + 1)
+        );
+
+        // The nodes left after the last line are counted
+        let mut short = String::new();
+        dag.format_tree(&mut short, usize::MAX, usize::MAX, 3)
+            .unwrap();
+        let left = dag.stats().reachable - 3;
+        assert_eq!(
+            format!("{}… ({} more nodes)\n", lines[..3].join("\n") + "\n", left),
+            short
+        );
+
+        let debug = format!("{dag:?}");
+        assert!(debug.starts_with("Dag { len: 4, nodes: "));
+        assert!(debug.ends_with(&full));
+    }
+
+    #[test]
+    fn test_debug_lines() {
+        let seqs = random_sequences(1, 0x9e37_79b9_7f4a_7c15, 3..=3, 40..=40, 4);
+        let seqs = seqs[0].iter().map(Vec::as_slice).collect::<Vec<_>>();
+        let dag = xmcsk(0, &seqs);
+
+        let mut full = String::new();
+        dag.format_tree(&mut full, DEBUG_DEPTH, DEBUG_WIDTH, usize::MAX)
+            .unwrap();
+        assert!(full.lines().count() > DEBUG_LINES);
+
+        // Header, lines of the tree and number of nodes left
+        let debug = format!("{dag:?}");
+        let lines = debug.lines().collect::<Vec<_>>();
+        assert_eq!(DEBUG_LINES + 2, lines.len());
+        assert!(full.starts_with(&lines[1..=DEBUG_LINES].join("\n")));
+        assert!(lines[DEBUG_LINES + 1].starts_with("… ("));
+        assert!(lines[DEBUG_LINES + 1].ends_with(" more nodes)"));
+    }
+}
@@ -2,20 +2,134 @@
 //! of maximal common subsequences of two sequences.
 //!
 
-use super::{Dag, Node, NodeType, Position};
-
+use super::budget::Usage;
+use super::intern::Interner;
+use super::memo::{map_bytes, Memo};
+use super::observer::Monitor;
+use super::{Dag, Node, NodeType, Nodes, Position};
+
+use crate::bounds::check_upper_bound;
+use crate::error::check_length;
+use crate::substr::{Embedding, SubString as SubSeq};
+use crate::Error;
+use std::cmp::{max, min};
 use std::collections::HashMap;
 
-use crate::substr::SubString as SubSeq;
-use std::cmp::{max, min};
+/// Largest gap of the gap-constrained mode, for `(gap + 1)^2` to fit in
+/// a `usize`: `2^32 - 2` on a 64-bit target
+const MAX_GAP: usize = (1 << (usize::BITS / 2)) - 2;
+
+/// Check that the distances stored in the memo keys of the
+/// gap-constrained mode cannot overflow
+///
+/// # Errors
+/// Returns [`Error::GapTooLarge`] if `gap` and one of the sequences are
+/// longer than [`MAX_GAP`].
+pub(super) fn check_gap<T>(gap: usize, sequences: &[&[T]]) -> Result<(), Error> {
+    let longest = sequences.iter().map(|s| s.len()).max().unwrap_or(0);
+    if min(gap, longest) > MAX_GAP {
+        return Err(Error::GapTooLarge { gap, max: MAX_GAP });
+    }
+
+    Ok(())
+}
 
 /// Intermediate structure used to compute the xMCS of two
 /// sequences as a directed acyclic graph
 struct Builder<'a, T> {
     /// Array of nodes
-    nodes: Vec<Node<'a, T>>,
+    nodes: Nodes<'a, T>,
     /// Used to remember if we already computed the result for a given node
-    memo: HashMap<Position, Option<usize>>,
+    memo: Memo,
+    /// Used to share the nodes with the same content
+    interner: Interner,
+    /// Lengths of the tails of the sequences where each End node was
+    /// created, the shortest ones if it was created several times, only
+    /// recorded for [`xmcs2_with_ends`]
+    tails: Option<Tails>,
+    /// Resources used by the enclosing computation
+    base: Usage,
+    /// Index of the sequence being added, reported when the graph is
+    /// full
+    sequence: usize,
+}
+
+/// Positions of the End nodes of a graph in the two sequences it was
+/// computed from, returned by [`xmcs2_with_ends`]
+///
+/// The suffix of an End node is a tail of one of the sequences, embedded
+/// in a tail of the other one. The tails where each End node was created
+/// are recorded while building the graph, and the embeddings are computed
+/// from the [`SubString`](crate::substr::SubString) table of the
+/// sequences used by the builder.
+pub struct Ends<'a, T> {
+    /// First sequence
+    s1: &'a [T],
+    /// Second sequence
+    s2: &'a [T],
+    /// Table used to build the graph
+    substr: SubSeq,
+    /// Lengths of the tails of the sequences for each End node
+    tails: Tails,
+}
+
+/// Nodes computed by a builder, index of the first node (`None` if the
+/// graph is empty) and number of nodes shared
+type Raw<'a, T> = (Nodes<'a, T>, Option<usize>, usize);
+
+/// Lengths of the tails of the sequences where each End node was created,
+/// by index of the node
+type Tails = HashMap<usize, (usize, usize)>;
+
+/// Pending call of [`Builder::compute`] or [`Builder::compute_gapped`]
+/// in their explicit call stack
+#[derive(Clone, Copy)]
+struct Call<'a, T> {
+    /// Minimum length of the subsequences
+    len: usize,
+    /// Tail of the first sequence
+    s1: &'a [T],
+    /// Tail of the second sequence
+    s2: &'a [T],
+    /// Distances from the last matched elements (gap-constrained mode)
+    dist: (usize, usize),
+    /// Union of the subgraphs computed so far (gap-constrained mode)
+    res: Option<usize>,
+    /// What the call is waiting for
+    step: Step,
+}
+
+/// Progress of a call, giving the meaning of the value returned by the
+/// last finished call when it is resumed
+#[derive(Clone, Copy)]
+enum Step {
+    /// Not started yet
+    Enter,
+    /// Subgraph after two matching elements
+    Element,
+    /// Subgraph without the first element of the first sequence
+    Skip1,
+    /// Subgraph without the first element of the second sequence, along
+    /// with the result of `Skip1`
+    Skip2(Option<usize>),
+}
+
+impl<'a, T> Call<'a, T> {
+    fn new(len: usize, s1: &'a [T], s2: &'a [T], dist: (usize, usize)) -> Self {
+        Self {
+            len,
+            s1,
+            s2,
+            dist,
+            res: None,
+            step: Step::Enter,
+        }
+    }
+
+    /// The same call, to be resumed at `step`
+    fn then(self, step: Step) -> Self {
+        Self { step, ..self }
+    }
 }
 
 impl<'a, T> Builder<'a, T>
@@ -27,97 +141,373 @@ where
     /// This function computes a directed acyclic graph (dag) used to
     /// represent an extended set of maximal common subsequences of
     /// length at least `len` of the two sequences `s1` and `s2`.
-    pub(super) fn build(len: usize, s1: &'a [T], s2: &'a [T]) -> Dag<'a, T> {
-        let (mut graph, start) = Self::build_raw(len, s1, s2);
-
-        // Set of common subsequences is empty
-        if start.is_none() {
-            // If there is no subsequence, the graph should be empty
-            assert_eq!(0, graph.len());
-
-            graph.push(Node {
-                min_length: 0,
-                max_length: 0,
-                inner: NodeType::Empty,
-            });
-        }
+    ///
+    /// Only fails with the `compact` feature, if the graph has too many
+    /// nodes.
+    pub(super) fn build(len: usize, s1: &'a [T], s2: &'a [T]) -> Result<Dag<'a, T>, Error> {
+        let substr = SubSeq::new(s1, s2, max(s1.len(), s2.len()) - len);
+        let (dag, _) = Self::build_with_tails(len, s1, s2, &substr, None)?;
+        Ok(dag)
+    }
 
-        Dag {
-            nodes: graph,
-            start: start.unwrap_or(0),
-            len,
-        }
+    /// Same as [`build`](Builder::build), but also returns the tails of
+    /// the sequences where the End nodes were created.
+    fn build_with_ends(
+        len: usize,
+        s1: &'a [T],
+        s2: &'a [T],
+    ) -> Result<(Dag<'a, T>, Ends<'a, T>), Error> {
+        let substr = SubSeq::new(s1, s2, max(s1.len(), s2.len()) - len);
+        let (dag, tails) = Self::build_with_tails(len, s1, s2, &substr, Some(Tails::new()))?;
+        let ends = Ends {
+            s1,
+            s2,
+            substr,
+            tails: tails.unwrap_or_default(),
+        };
+
+        Ok((dag, ends))
     }
 
-    fn build_raw(len: usize, s1: &'a [T], s2: &'a [T]) -> (Vec<Node<'a, T>>, Option<usize>) {
-        let n = max(s1.len(), s2.len());
-        let delta = n - len;
-        let subseq = SubSeq::new(s1, s2, delta);
+    /// Compute the graph, recording the tails of its End nodes in `tails`
+    /// if it is not `None`
+    fn build_with_tails(
+        len: usize,
+        s1: &'a [T],
+        s2: &'a [T],
+        substr: &SubSeq,
+        tails: Option<Tails>,
+    ) -> Result<(Dag<'a, T>, Option<Tails>), Error> {
+        let mut monitor = Monitor::unlimited(2);
+        let mut res = Self::new(len, s1, s2, &monitor, Usage::default());
+        res.tails = tails;
+
+        let start = res.compute(&mut monitor, len, s1, s2, substr)?;
+        let dag = Dag::from_raw(res.nodes, start, len, res.interner.shared());
+        Ok((dag, res.tails))
+    }
 
-        let mut res = Self {
-            nodes: Vec::new(),
-            memo: HashMap::new(),
-        };
+    /// Compute a dag that represent the set of common subsequences of
+    /// length at least `len` of `s1` and `s2` whose consecutive elements
+    /// are at most `gap` positions apart in both sequences, reporting the
+    /// resources used to `monitor`.
+    pub(super) fn build_gapped(
+        len: usize,
+        gap: usize,
+        s1: &'a [T],
+        s2: &'a [T],
+        monitor: &mut Monitor<'_>,
+    ) -> Result<Dag<'a, T>, Error> {
+        check_gap(gap, &[s1, s2])?;
+        // A distance never exceeds the length of the sequences, so a
+        // larger gap is the same as no constraint, and clamping it keeps
+        // the pairs of distances stored in the memo keys distinct
+        let gap = min(gap, max(s1.len(), s2.len()));
+
+        let mut res = Self::with_memo(Memo::sparse(), monitor, Usage::default());
+
+        let start = res.compute_gapped(monitor, len, s1, s2, gap, (0, 0))?;
+        Ok(Dag::from_raw(res.nodes, start, len, res.interner.shared()))
+    }
 
-        let start = res.compute(len, s1, s2, &subseq);
-        (res.nodes, start)
+    /// Compute the nodes of the graph, the index of its first node and
+    /// the number of nodes shared, reporting to `monitor` the resources
+    /// used in addition to `base`
+    ///
+    /// `substr` must answer the queries on the tails of `s1` and `s2`, in
+    /// a band of width at least `max(|s1|, |s2|) - len`: it may have been
+    /// built for longer sequences ending with `s1` and `s2`.
+    fn build_raw(
+        len: usize,
+        s1: &'a [T],
+        s2: &'a [T],
+        substr: &SubSeq,
+        monitor: &mut Monitor<'_>,
+        base: Usage,
+    ) -> Result<Raw<'a, T>, Error> {
+        let mut res = Self::new(len, s1, s2, monitor, base);
+        let start = res.compute(monitor, len, s1, s2, substr)?;
+        Ok((res.nodes, start, res.interner.shared()))
     }
 
-    /// Recursively compute the graph
-    fn compute(&mut self, len: usize, s1: &'a [T], s2: &'a [T], substr: &SubSeq) -> Option<usize> {
-        let l1 = s1.len();
-        let l2 = s2.len();
-        let pos = Position(len, l1, l2);
+    /// Builder of the graph of `s1` and `s2` for the subsequences of
+    /// length at least `len`, whose memo fits in the budget of `monitor`
+    fn new(len: usize, s1: &[T], s2: &[T], monitor: &Monitor<'_>, base: Usage) -> Self {
+        // A dense table larger than the budget would fail immediately,
+        // while a hash table only grows with the positions visited
+        let memo = Memo::sequences(len, s1.len(), s2.len(), |bytes| monitor.allows_bytes(bytes));
+        Self::with_memo(memo, monitor, base)
+    }
 
-        // Value already computed once, return its index in the array
-        if let Some(&index) = self.memo.get(&pos) {
-            return index;
+    fn with_memo(memo: Memo, monitor: &Monitor<'_>, base: Usage) -> Self {
+        Self {
+            nodes: Nodes::default(),
+            memo,
+            interner: Interner::default(),
+            tails: None,
+            base,
+            sequence: monitor.sequence,
         }
+    }
 
-        // Empty set. Empty node is always at index 0
-        if len > l1 || len > l2 {
-            self.insert_empty_at(pos);
-            return None;
+    /// Compute the graph, using an explicit stack of calls so that long
+    /// sequences do not overflow the thread stack
+    fn compute(
+        &mut self,
+        monitor: &mut Monitor<'_>,
+        len: usize,
+        s1: &'a [T],
+        s2: &'a [T],
+        substr: &SubSeq,
+    ) -> Result<Option<usize>, Error> {
+        let mut stack = vec![Call::new(len, s1, s2, (0, 0))];
+        // Value returned by the last finished call
+        let mut ret = None;
+
+        while let Some(call) = stack.pop() {
+            let Call { len, s1, s2, .. } = call;
+            let l1 = s1.len();
+            let l2 = s2.len();
+            let pos = Position(len, l1, l2, 0);
+
+            match call.step {
+                Step::Enter => {
+                    // Empty set, checked first as it is cheaper than the
+                    // memo and keeps the positions stored in the memo in
+                    // its band
+                    if len > l1 || len > l2 {
+                        ret = None;
+                        continue;
+                    }
+
+                    // Value already computed once, return its index in the array
+                    if let Some(index) = self.memo.get(pos) {
+                        ret = index;
+                        continue;
+                    }
+
+                    monitor.step(|| self.usage())?;
+
+                    if substr.is_substring_from_end(l1, l2) {
+                        // One sequence a substring of the other
+                        ret = self.compute_subseq_node(s1, l1, s2, l2, pos)?;
+                    } else if s1[0] == s2[0] {
+                        // Matching elements
+                        // s1 and s2 always contains at least one element
+                        // otherwise one is a subsequence of the other.
+                        let len = len.saturating_sub(1);
+                        stack.push(call.then(Step::Element));
+                        stack.push(Call::new(len, &s1[1..], &s2[1..], (0, 0)));
+                    } else {
+                        // Mismatching elements
+                        stack.push(call.then(Step::Skip1));
+                        stack.push(Call::new(len, &s1[1..], s2, (0, 0)));
+                    }
+                }
+                Step::Element => ret = self.compute_common_element_node(s1[0], ret, pos)?,
+                Step::Skip1 => {
+                    stack.push(call.then(Step::Skip2(ret)));
+                    stack.push(Call::new(len, s1, &s2[1..], (0, 0)));
+                }
+                Step::Skip2(index1) => ret = self.compute_split_node(index1, ret, pos)?,
+            }
         }
 
-        if substr.is_substring_from_end(l1, l2) {
-            // One sequence a substring of the other
-            self.compute_subseq_node(s1, l1, s2, l2, pos)
-        } else if s1[0] == s2[0] {
-            // Matching elements
-            // s1 and s2 always contains at least one element
-            // otherwise one is a subsequence of the other.
-
-            let len = len.saturating_sub(1);
-            let index = self.compute(len, &s1[1..], &s2[1..], substr);
+        Ok(ret)
+    }
 
-            self.compute_common_element_node(s1[0], index, pos)
-        } else {
-            // Mismatching elements
+    /// Compute the graph of the common subsequences whose consecutive
+    /// elements are at most `gap` positions apart
+    ///
+    /// `dist` holds, for each sequence, the distance between the last
+    /// matched element and the first element of the tail, or `(0, 0)`
+    /// if no element was matched yet. Both distances are at most `gap`,
+    /// which must be at most [`MAX_GAP`].
+    fn compute_gapped(
+        &mut self,
+        monitor: &mut Monitor<'_>,
+        len: usize,
+        s1: &'a [T],
+        s2: &'a [T],
+        gap: usize,
+        dist: (usize, usize),
+    ) -> Result<Option<usize>, Error> {
+        let mut stack = vec![Call::new(len, s1, s2, dist)];
+        // Value returned by the last finished call
+        let mut ret = None;
+
+        while let Some(mut call) = stack.pop() {
+            let Call {
+                len, s1, s2, dist, ..
+            } = call;
+            let l1 = s1.len();
+            let l2 = s2.len();
+            let pos = Position(len, l1, l2, dist.0 * (gap + 1) + dist.1);
+            let started = dist != (0, 0);
+
+            match call.step {
+                Step::Enter => {
+                    // Empty set
+                    if len > l1 || len > l2 {
+                        ret = None;
+                        continue;
+                    }
+
+                    // Value already computed once, return its index in the array
+                    if let Some(index) = self.memo.get(pos) {
+                        ret = index;
+                        continue;
+                    }
+
+                    monitor.step(|| self.usage())?;
+
+                    // The subsequence is long enough, it can end here
+                    if len == 0 {
+                        call.res = Some(self.end_node()?);
+                    }
+
+                    // Matching elements, if they are not too far from the
+                    // previous ones
+                    if l1 > 0
+                        && l2 > 0
+                        && s1[0] == s2[0]
+                        && (!started || max(dist.0, dist.1) <= gap)
+                    {
+                        let len = len.saturating_sub(1);
+                        stack.push(call.then(Step::Element));
+                        stack.push(Call::new(len, &s1[1..], &s2[1..], (1, 1)));
+                        continue;
+                    }
+                }
+                Step::Element => {
+                    let index = ret.map(|i| self.push_element(s1[0], i)).transpose()?;
+                    call.res = self.union(call.res, index)?;
+                }
+                Step::Skip1 | Step::Skip2(_) => call.res = self.union(call.res, ret)?,
+            }
 
-            let index1 = self.compute(len, &s1[1..], s2, substr);
-            let index2 = self.compute(len, s1, &s2[1..], substr);
+            // Skip an element of one of the sequences, only if another
+            // element can still be matched after it
+            if l1 > 0 && l2 > 0 {
+                if matches!(call.step, Step::Enter | Step::Element) && (!started || dist.0 < gap) {
+                    let d = if started { dist.0 + 1 } else { 0 };
+                    stack.push(call.then(Step::Skip1));
+                    stack.push(Call::new(len, &s1[1..], s2, (d, dist.1)));
+                    continue;
+                }
+                if !matches!(call.step, Step::Skip2(_)) && (!started || dist.1 < gap) {
+                    let d = if started { dist.1 + 1 } else { 0 };
+                    stack.push(call.then(Step::Skip2(None)));
+                    stack.push(Call::new(len, s1, &s2[1..], (dist.0, d)));
+                    continue;
+                }
+            }
 
-            self.compute_split_node(index1, index2, pos)
+            self.memo.insert(pos, call.res);
+            ret = call.res;
         }
+
+        Ok(ret)
+    }
+
+    /// Resources used so far, including those of the enclosing
+    /// computation
+    fn usage(&self) -> Usage {
+        let bytes = self.nodes.bytes()
+            + self.memo.bytes()
+            + self.interner.bytes()
+            + self.tails.as_ref().map_or(0, map_bytes);
+
+        self.base
+            + Usage {
+                nodes: self.nodes.len(),
+                memo_entries: self.memo.len(),
+                bytes,
+            }
     }
 
-    /// Insert a node into the dag, remember to what parameters it correspond
-    /// and returns its index.
-    fn insert_node_at(&mut self, position: Position, node: Node<'a, T>) -> Option<usize> {
-        let index = Some(self.nodes.len());
-        self.nodes.push(node);
+    /// Insert a node into the dag, or find an existing node with the
+    /// same content, remember to what parameters it correspond and
+    /// returns its index.
+    fn insert_node_at(
+        &mut self,
+        position: Position,
+        node: Node<'a, T>,
+    ) -> Result<Option<usize>, Error> {
+        let index = Some(self.push_node(node)?);
         self.memo.insert(position, index);
-        index
+        Ok(index)
+    }
+
+    /// Insert `node` into the graph without registering its position,
+    /// or find an existing node with the same content, and return its
+    /// index
+    ///
+    /// Fails with the `compact` feature if the graph is full.
+    fn push_node(&mut self, node: Node<'a, T>) -> Result<usize, Error> {
+        self.interner
+            .insert(&mut self.nodes, node)
+            .ok_or_else(|| self.usage().exceeded(self.sequence))
+    }
+
+    /// Returns the index of the node representing the empty sequence,
+    /// inserting it if needed.
+    fn end_node(&mut self) -> Result<usize, Error> {
+        self.push_node(Node {
+            max_length: 0,
+            min_length: 0,
+            inner: NodeType::End { suffix: &[] },
+        })
+    }
+
+    /// Insert an element node pointing to `child` and returns its index
+    fn push_element(&mut self, value: T, child: usize) -> Result<usize, Error> {
+        let node = self.nodes.get(child);
+        let node = Node {
+            max_length: node.max_length + 1,
+            min_length: node.min_length + 1,
+            inner: NodeType::Element { value, child },
+        };
+
+        self.push_node(node)
+    }
+
+    /// Returns the index of a node representing the union of two
+    /// subgraphs, inserting a split node if needed.
+    fn union(
+        &mut self,
+        index1: Option<usize>,
+        index2: Option<usize>,
+    ) -> Result<Option<usize>, Error> {
+        match (index1, index2) {
+            (None, None) => Ok(None),
+            (Some(i), None) | (None, Some(i)) => Ok(Some(i)),
+            (Some(i1), Some(i2)) if i1 == i2 => Ok(Some(i1)),
+            (Some(i1), Some(i2)) => {
+                let node1 = self.nodes.get(i1);
+                let node2 = self.nodes.get(i2);
+
+                let node = Node {
+                    max_length: max(node1.max_length, node2.max_length),
+                    min_length: min(node1.min_length, node2.min_length),
+                    inner: NodeType::Split {
+                        child1: i1,
+                        child2: i2,
+                    },
+                };
+
+                self.push_node(node).map(Some)
+            }
+        }
     }
 
     /// Register that a position points to an existing node
     ///
     /// Panics if that node does not exists
-    fn points_to_node(&mut self, position: Position, index: usize) -> Option<usize> {
+    fn points_to_node(&mut self, position: Position, index: usize) -> usize {
         assert!(index < self.nodes.len());
         self.memo.insert(position, Some(index));
-        Some(index)
+        index
     }
 
     /// Register that a node is empty
@@ -136,7 +526,7 @@ where
         s2: &'a [T],
         l2: usize,
         position: Position,
-    ) -> Option<usize> {
+    ) -> Result<Option<usize>, Error> {
         let (length, suffix) = if l1 < l2 { (l1, s1) } else { (l2, s2) };
 
         let node = Node {
@@ -145,7 +535,15 @@ where
             inner: NodeType::End { suffix },
         };
 
-        self.insert_node_at(position, node)
+        let index = self.push_node(node)?;
+        self.memo.insert(position, Some(index));
+        // The embedding in the shortest tails comes after the elements
+        // before the node on every path leading to it
+        if let Some(tails) = &mut self.tails {
+            let tails = tails.entry(index).or_insert((l1, l2));
+            *tails = (min(tails.0, l1), min(tails.1, l2));
+        }
+        Ok(Some(index))
     }
 
     /// Compute the next node in the case where the two sequences have an
@@ -156,14 +554,14 @@ where
         element: T,
         index: Option<usize>,
         position: Position,
-    ) -> Option<usize> {
+    ) -> Result<Option<usize>, Error> {
         match index {
             // Child is empty, the new node is also empty.
-            None => self.insert_empty_at(position),
+            None => Ok(self.insert_empty_at(position)),
 
             // Else the new node references the child.
             Some(i) => {
-                let node = &self.nodes[i];
+                let node = self.nodes.get(i);
 
                 let max_length = node.max_length + 1;
                 let min_length = node.min_length + 1;
@@ -188,19 +586,19 @@ where
         index1: Option<usize>,
         index2: Option<usize>,
         position: Position,
-    ) -> Option<usize> {
+    ) -> Result<Option<usize>, Error> {
         match (index1, index2) {
             // Both children are empty nodes, the new node is also empty.
-            (None, None) => self.insert_empty_at(position),
+            (None, None) => Ok(self.insert_empty_at(position)),
 
             // One of the children is an empty node,
             // the new node can point to the other.
-            (None, Some(i)) | (Some(i), None) => self.points_to_node(position, i),
+            (None, Some(i)) | (Some(i), None) => Ok(Some(self.points_to_node(position, i))),
 
             // Else the new node references the two children.
             (Some(i1), Some(i2)) => {
-                let node1 = &self.nodes[i1];
-                let node2 = &self.nodes[i2];
+                let node1 = self.nodes.get(i1);
+                let node2 = self.nodes.get(i2);
 
                 let max_length = max(node1.max_length, node2.max_length);
                 let min_length = min(node1.min_length, node2.min_length);
@@ -223,20 +621,274 @@ where
 /// Compute a graph representing an extended set of maximal
 /// common subsequences of length at least `len` of two sequences
 /// `s1` and `s2`.
+///
+/// # Panics
+/// With the `compact` feature, panics if the graph has more than `2^31`
+/// nodes.
 pub fn xmcs2<'a, T>(len: usize, s1: &'a [T], s2: &'a [T]) -> Dag<'a, T>
 where
     T: Eq + Copy,
 {
+    match Builder::build(len, s1, s2) {
+        Ok(dag) => dag,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Same as [`xmcs2`], but returns an error instead of panicking if the
+/// parameters are invalid.
+///
+/// # Errors
+/// Returns [`Error::LengthTooLarge`] if `len` is larger than the length
+/// of one of the sequences, or than the bound on the length of a longest
+/// common subsequence of
+/// [`check_upper_bound`](crate::bounds::check_upper_bound). With the
+/// `compact` feature, returns [`Error::BudgetExceeded`] if the graph has
+/// more than `2^31` nodes.
+pub fn try_xmcs2<'a, T>(len: usize, s1: &'a [T], s2: &'a [T]) -> Result<Dag<'a, T>, Error>
+where
+    T: Eq + Copy,
+{
+    check_length(len, &[s1, s2])?;
+    check_upper_bound(len, &[s1, s2])?;
     Builder::build(len, s1, s2)
 }
 
+/// Compute a graph representing the set of common subsequences
+/// of length at least `len` of two sequences `s1` and `s2`, whose
+/// consecutive elements are at most `gap` positions apart in both
+/// sequences.
+///
+/// Consecutive elements at positions `p < q` satisfy the constraint if
+/// `q - p <= gap`: with `gap = 1` the subsequences are contiguous, and
+/// with `gap = 0` no two elements can follow each other, so only the
+/// subsequences of at most one element remain. A gap at least as long
+/// as the sequences does not constrain anything.
+///
+/// Unlike [`xmcs2`], the graph does not only contain maximal common
+/// subsequences: a subsequence of a gap-constrained common subsequence
+/// may not satisfy the constraint.
+///
+/// # Examples
+/// ```
+/// // "ABCD" can be found in "AXBXCXD" with gaps of 2 positions
+/// let dag = xmcs::dag::xmcs2_with_gap(2, 2, b"ABCD", b"AXBXCXD");
+/// assert_eq!(Some(b"ABCD".to_vec()), dag.extract_lcs());
+///
+/// // But not with gaps of 1 position
+/// let dag = xmcs::dag::xmcs2_with_gap(2, 1, b"ABCD", b"AXBXCXD");
+/// assert_eq!(None, dag.extract_lcs());
+/// ```
+///
+/// # Panics
+/// Panics if `gap` and one of the sequences are longer than `2^32 - 2`
+/// on a 64-bit target. With the `compact` feature, panics if the graph
+/// has more than `2^31` nodes.
+pub fn xmcs2_with_gap<'a, T>(len: usize, gap: usize, s1: &'a [T], s2: &'a [T]) -> Dag<'a, T>
+where
+    T: Eq + Copy,
+{
+    match Builder::build_gapped(len, gap, s1, s2, &mut Monitor::unlimited(2)) {
+        Ok(dag) => dag,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Same as [`xmcs2_with_gap`], but returns an error instead of panicking
+/// if the parameters are invalid.
+///
+/// # Errors
+/// Same as [`try_xmcs2`], and returns [`Error::GapTooLarge`] if `gap`
+/// and one of the sequences are longer than `2^32 - 2` on a 64-bit
+/// target.
+pub fn try_xmcs2_with_gap<'a, T>(
+    len: usize,
+    gap: usize,
+    s1: &'a [T],
+    s2: &'a [T],
+) -> Result<Dag<'a, T>, Error>
+where
+    T: Eq + Copy,
+{
+    check_length(len, &[s1, s2])?;
+    check_gap(gap, &[s1, s2])?;
+    check_upper_bound(len, &[s1, s2])?;
+    Builder::build_gapped(len, gap, s1, s2, &mut Monitor::unlimited(2))
+}
+
+/// Compute the graph of [`xmcs2_with_gap`], reporting the resources used
+/// to `monitor`, which may stop the computation
+pub(super) fn xmcs2_with_gap_monitored<'a, T>(
+    len: usize,
+    gap: usize,
+    s1: &'a [T],
+    s2: &'a [T],
+    monitor: &mut Monitor<'_>,
+) -> Result<Dag<'a, T>, Error>
+where
+    T: Eq + Copy,
+{
+    Builder::build_gapped(len, gap, s1, s2, monitor)
+}
+
+/// Compute the nodes of the graph of [`xmcs2`], the index of its first
+/// node and the number of nodes shared
+///
+/// `substr` answers whether a tail of `s1` or `s2` is a subsequence of
+/// the other (see [`Builder::build_raw`]). The resources used, added to
+/// `base`, are reported to `monitor`, which may stop the computation.
 pub(super) fn xmcs2_raw<'a, T>(
     len: usize,
     s1: &'a [T],
     s2: &'a [T],
-) -> (Vec<Node<'a, T>>, Option<usize>)
+    substr: &SubSeq,
+    monitor: &mut Monitor<'_>,
+    base: Usage,
+) -> Result<Raw<'a, T>, Error>
 where
     T: Eq + Copy,
 {
-    Builder::build_raw(len, s1, s2)
+    Builder::build_raw(len, s1, s2, substr, monitor, base)
+}
+
+/// Same as [`xmcs2`], but also returns the positions of the End nodes
+/// of the graph in `s1` and `s2`.
+///
+/// # Examples
+/// ```
+/// let (s1, s2) = (b"ABCD", b"ACBD");
+/// let (dag, ends) = xmcs::dag::xmcs2_with_ends(3, s1, s2);
+/// assert_eq!(dag.to_set(), xmcs::dag::xmcs2(3, s1, s2).to_set());
+///
+/// // "CD" is a tail of `s1`, found at the end of `s2`, and "BD" a tail
+/// // of `s2`, found at the end of `s1`
+/// let positions = ends.positions();
+/// assert_eq!(vec![(0, vec![(2, 1), (3, 3)]), (1, vec![(1, 2), (3, 3)])], positions);
+/// ```
+///
+/// # Panics
+/// Same as [`xmcs2`].
+pub fn xmcs2_with_ends<'a, T>(len: usize, s1: &'a [T], s2: &'a [T]) -> (Dag<'a, T>, Ends<'a, T>)
+where
+    T: Eq + Copy,
+{
+    match Builder::build_with_ends(len, s1, s2) {
+        Ok(res) => res,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+impl<T> Ends<'_, T>
+where
+    T: Eq,
+{
+    /// Index of each End node of the graph, with the pairs of positions
+    /// `(p1, p2)` of the elements of its suffix in the two sequences,
+    /// sorted by index
+    ///
+    /// The positions are given by
+    /// [`SubString::embedding_at`](crate::substr::SubString::embedding_at)
+    /// on the tails where the node was created, the shortest ones if it
+    /// was created several times. They come after the positions of the
+    /// elements before the node, on any path leading to it.
+    ///
+    /// Runs in time `O(|s1| + |s2|)` for each End node.
+    pub fn positions(&self) -> Vec<(usize, Embedding)> {
+        let (d1, d2) = (self.s1.len(), self.s2.len());
+        let mut res = self
+            .tails
+            .iter()
+            .filter_map(|(&idx, &(l1, l2))| {
+                let embedding = self.substr.embedding_at(self.s1, self.s2, d1 - l1, d2 - l2)?;
+                Some((idx, embedding))
+            })
+            .collect::<Vec<_>>();
+        res.sort_unstable_by_key(|&(idx, _)| idx);
+
+        res
+    }
+}
+
+// === Tests ===
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_end_positions() {
+        let (s1, s2) = (b"ADBCBAD", b"ADCBACD");
+        let (dag, ends) = xmcs2_with_ends(3, s1, s2);
+        assert_eq!(xmcs2(3, s1, s2).to_set(), dag.to_set());
+
+        // Every End node has positions
+        let positions = ends.positions();
+        let end_nodes = dag
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| matches!(node.inner, NodeType::End { .. }))
+            .map(|(idx, _)| idx);
+        assert!(positions.iter().map(|&(idx, _)| idx).eq(end_nodes));
+
+        for (idx, embedding) in positions {
+            let NodeType::End { suffix } = dag.nodes.get(idx).inner else {
+                unreachable!()
+            };
+            assert_eq!(suffix.len(), embedding.len());
+            for (e, &(p1, p2)) in suffix.iter().zip(&embedding) {
+                assert_eq!(*e, s1[p1]);
+                assert_eq!(*e, s2[p2]);
+            }
+            // Either the positions in `s1` or in `s2` are a tail
+            let tail1 = embedding
+                .iter()
+                .map(|p| p.0)
+                .eq(s1.len() - suffix.len()..s1.len());
+            let tail2 = embedding
+                .iter()
+                .map(|p| p.1)
+                .eq(s2.len() - suffix.len()..s2.len());
+            assert!(tail1 || tail2);
+        }
+
+        let (_, ends) = xmcs2_with_ends(3, b"ABC", b"DEF");
+        assert!(ends.positions().is_empty());
+    }
+
+    #[test]
+    fn test_unbounded_gap() {
+        let (s1, s2) = (b"ADBCBAD", b"ADCBACD");
+
+        // A gap larger than the sequences does not constrain anything
+        let bounded = xmcs2_with_gap(2, s2.len(), s1, s2).to_set();
+        let unbounded = xmcs2_with_gap(2, usize::MAX, s1, s2).to_set();
+        assert_eq!(bounded, unbounded);
+        assert!(unbounded.contains(&b"ADBAD"[..]));
+        assert!(unbounded.contains(&b"ABCD"[..]));
+    }
+
+    #[test]
+    fn test_gap_too_large() {
+        // Zero-sized elements, nothing is allocated
+        let long = vec![(); MAX_GAP + 1];
+        let short = [(); 2];
+
+        let err = Error::GapTooLarge {
+            gap: usize::MAX,
+            max: MAX_GAP,
+        };
+        let res = try_xmcs2_with_gap(1, usize::MAX, &long, &short);
+        assert_eq!(Some(err), res.err());
+        let res = crate::dag::try_xmcsk_with_gap(1, usize::MAX, &[&short, &long]);
+        assert_eq!(Some(err), res.err());
+
+        // The requested gap is reported
+        let err = Error::GapTooLarge {
+            gap: MAX_GAP + 1,
+            max: MAX_GAP,
+        };
+        let res = try_xmcs2_with_gap(1, MAX_GAP + 1, &long, &short);
+        assert_eq!(Some(err), res.err());
+    }
 }
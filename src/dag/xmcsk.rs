@@ -2,145 +2,585 @@
 //!  subsequences of k sequences
 //!
 
-use super::{Dag, Node, NodeType, Position};
+use super::budget::{Budget, Usage};
+use super::intern::Interner;
+use super::memo::Memo;
+use super::observer::{Monitor, Observer};
+use super::tables::Tables;
+use super::{check_gap, Dag, Node, NodeType, Nodes, Position};
 
 use std::cmp::{max, min};
-use std::collections::HashMap;
+use std::hash::Hash;
+use std::mem::size_of;
+
+use crate::automaton::Automaton;
+use crate::bounds::check_upper_bound;
+use crate::error::check_length;
+use crate::Error;
+
+/// Maximum number of entries of the automaton of a sequence added to the
+/// graph, above which its End nodes are always expanded with the
+/// algorithm for two sequences
+const AUTOMATON_LIMIT: usize = 1 << 22;
+
+/// Lookup of [`Automaton::is_subsequence_from`], only available for
+/// hashable elements, stored with the automaton so that the builder only
+/// requires `T: Eq`
+type IsSubsequence<T> = fn(&Automaton<T>, usize, &[T]) -> bool;
+
+/// Function adding a sequence to a graph
+type AddSequence<'a, T> =
+    fn(Dag<'a, T>, &'a [T], &mut Monitor<'_>) -> Result<Dag<'a, T>, Error>;
 
 /// Intermediate structure used to compute an extended set of
 /// common subsequences of a sequence and a graph representing
 /// a set of subsequences
 struct Builder<'a, T> {
     /// Array of nodes
-    nodes: Vec<Node<'a, T>>,
+    nodes: Nodes<'a, T>,
     /// Used to remember if we already computed the result for a given node
-    memo: HashMap<Position, Option<usize>>,
+    memo: Memo,
     /// Graph representing a set of sequences
-    base_graph: Vec<Node<'a, T>>,
+    base_graph: Nodes<'a, T>,
+    /// Used to share the nodes with the same content
+    interner: Interner,
+    /// Subsequence tables of the End nodes
+    tables: Tables<'a, T>,
+    /// Automaton of the sequence being added and its lookup, if the
+    /// elements are hashable and the automaton small enough
+    automaton: Option<(Automaton<T>, IsSubsequence<T>)>,
+    /// Number of nodes shared while building the base graph and the
+    /// subgraphs computed with the algorithm for two sequences
+    shared: usize,
+    /// Index of the sequence being added, reported when the graph is
+    /// full
+    sequence: usize,
+}
+
+/// Pending call of [`Builder::compute`] or [`Builder::compute_gapped`]
+/// in their explicit call stack
+#[derive(Clone, Copy)]
+struct Call<'a, T> {
+    /// Minimum length of the subsequences
+    len: usize,
+    /// Node of the base graph
+    current: usize,
+    /// Tail of the sequence
+    seq: &'a [T],
+    /// Distance from the last matched element (gap-constrained mode)
+    dist: usize,
+    /// Subgraph computed so far (gap-constrained mode)
+    res: Option<usize>,
+    /// What the call is waiting for
+    step: Step,
+}
+
+/// Progress of a call, giving the meaning of the value returned by the
+/// last finished call when it is resumed
+#[derive(Clone, Copy)]
+enum Step {
+    /// Not started yet
+    Enter,
+    /// Subgraph after matching elements
+    Element,
+    /// Subgraph after matching elements (gap-constrained mode)
+    Match,
+    /// Subgraph skipping an element of the sequence (gap-constrained
+    /// mode)
+    Skip,
+    /// First subgraph of a union
+    First,
+    /// Second subgraph of a union, along with the first one
+    Second(Option<usize>),
+}
+
+impl<'a, T> Call<'a, T> {
+    fn new(len: usize, current: usize, seq: &'a [T], dist: usize) -> Self {
+        Self {
+            len,
+            current,
+            seq,
+            dist,
+            res: None,
+            step: Step::Enter,
+        }
+    }
+
+    /// The same call, to be resumed at `step`
+    fn then(self, step: Step) -> Self {
+        Self { step, ..self }
+    }
+}
+
+impl<'a, T> Builder<'a, T>
+where
+    T: Eq + Hash + Copy,
+{
+    /// Same as [`add_sequence`](Builder::add_sequence), but uses the
+    /// automaton of `sequence` to keep the End nodes whose suffix is a
+    /// subsequence of the tail of `sequence` without expanding them.
+    pub(super) fn add_sequence_with_automaton(
+        xmcs: Dag<'a, T>,
+        sequence: &'a [T],
+        monitor: &mut Monitor<'_>,
+    ) -> Result<Dag<'a, T>, Error> {
+        // Only an optimisation, skipped if the largest table allowed
+        // would not fit in the budget
+        let automaton = if monitor.allows_bytes(AUTOMATON_LIMIT * size_of::<u32>()) {
+            Automaton::with_limit(sequence, AUTOMATON_LIMIT)
+        } else {
+            None
+        };
+        let automaton = automaton.map(|a| (a, Automaton::is_subsequence_from as IsSubsequence<T>));
+
+        Self::add(xmcs, sequence, automaton, monitor)
+    }
 }
 
 impl<'a, T> Builder<'a, T>
 where
     T: Eq + Copy,
 {
-    pub(super) fn add_sequence(xmcs: Dag<'a, T>, sequence: &'a [T]) -> Dag<'a, T> {
+    /// Keep only the sequences of `xmcs` that are subsequences of
+    /// `sequence`, reporting the resources used to `monitor`, which may
+    /// stop the computation.
+    pub(super) fn add_sequence(
+        xmcs: Dag<'a, T>,
+        sequence: &'a [T],
+        monitor: &mut Monitor<'_>,
+    ) -> Result<Dag<'a, T>, Error> {
+        Self::add(xmcs, sequence, None, monitor)
+    }
+
+    fn add(
+        xmcs: Dag<'a, T>,
+        sequence: &'a [T],
+        automaton: Option<(Automaton<T>, IsSubsequence<T>)>,
+        monitor: &mut Monitor<'_>,
+    ) -> Result<Dag<'a, T>, Error> {
         let len = xmcs.len;
         let start = xmcs.start;
 
-        let mut res = Builder {
-            nodes: Vec::new(),
-            memo: HashMap::new(),
-            base_graph: xmcs.nodes,
-        };
+        // A dense table larger than the budget would fail immediately,
+        // while a hash table only grows with the positions visited
+        let memo = Memo::graph(len, xmcs.nodes.len(), sequence.len(), |bytes| {
+            monitor.allows_bytes(bytes)
+        });
+        let mut res = Self::new(xmcs, memo, monitor);
+        res.automaton = automaton;
+        let start = res.compute(monitor, len, start, sequence)?;
+        monitor.added(res.usage())?;
+
+        Ok(res.finish(start, len))
+    }
 
-        let start = res.compute(len, start, sequence);
+    /// Keep only the sequences of `xmcs` that can be found in `sequence`
+    /// with consecutive elements at most `gap` positions apart, reporting
+    /// the resources used to `monitor`, which may stop the computation.
+    pub(super) fn add_sequence_gapped(
+        xmcs: Dag<'a, T>,
+        gap: usize,
+        sequence: &'a [T],
+        monitor: &mut Monitor<'_>,
+    ) -> Result<Dag<'a, T>, Error> {
+        let len = xmcs.len;
+        let start = xmcs.start;
 
-        if start.is_none() {
-            assert_eq!(true, res.nodes.is_empty());
+        let mut res = Self::new(xmcs, Memo::sparse(), monitor);
+        let start = res.compute_gapped(monitor, len, start, sequence, gap, 0)?;
+        monitor.added(res.usage())?;
 
-            res.nodes.push(Node {
-                max_length: 0,
-                min_length: 0,
-                inner: NodeType::Empty,
-            });
+        Ok(res.finish(start, len))
+    }
+
+    fn new(xmcs: Dag<'a, T>, memo: Memo, monitor: &Monitor<'_>) -> Self {
+        Self {
+            nodes: Nodes::default(),
+            memo,
+            base_graph: xmcs.nodes,
+            interner: Interner::default(),
+            tables: Tables::default(),
+            automaton: None,
+            shared: xmcs.shared,
+            sequence: monitor.sequence,
         }
+    }
 
-        Dag {
-            nodes: res.nodes,
-            start: start.unwrap_or(0),
-            len,
+    /// Resources used so far, including the base graph
+    fn usage(&self) -> Usage {
+        let bytes = self.nodes.bytes()
+            + self.base_graph.bytes()
+            + self.memo.bytes()
+            + self.interner.bytes()
+            + self.tables.bytes()
+            + self.automaton.as_ref().map_or(0, |(a, _)| a.bytes());
+
+        Usage {
+            nodes: self.nodes.len(),
+            memo_entries: self.memo.len(),
+            bytes,
         }
     }
 
+    fn finish(self, start: Option<usize>, len: usize) -> Dag<'a, T> {
+        let shared = self.shared + self.interner.shared();
+        Dag::from_raw(self.nodes, start, len, shared)
+    }
+}
+
+impl<'a, T> Builder<'a, T>
+where
+    T: Eq + Copy,
+{
     // Compute (or retrieve if already calculated) the part of the graph
     // representing all the subsequences possible from the given minimum
     // length of subsequence `len`, the subgraph starting at `node_index`
     // and the sequence `seq`
-    fn compute(&mut self, len: usize, current: usize, seq: &'a [T]) -> Option<usize> {
-        let node = &self.base_graph[current];
-        let l1 = node.max_length;
-        let l2 = seq.len();
-        let pos = Position(len, l1, l2);
-
-        // TODO: maybe swap the next two conditions ?
-        // there may be a lot of case where len > l1 or len > l2
-        // and it is probably faster to test it than to retrieve
-        // an element from the hashmap
-        // TODO: benchmark
-
-        // Value already computed once, return its index in the array
-        if let Some(&index) = self.memo.get(&pos) {
-            return index;
-        }
-
-        // Empty set. Empty node is always at index 0
-        if len > l1 || len > l2 {
-            return self.insert_empty_at(pos);
-        }
+    //
+    // Uses an explicit stack of calls so that long sequences do not
+    // overflow the thread stack
+    fn compute(
+        &mut self,
+        monitor: &mut Monitor<'_>,
+        len: usize,
+        current: usize,
+        seq: &'a [T],
+    ) -> Result<Option<usize>, Error> {
+        let mut stack = vec![Call::new(len, current, seq, 0)];
+        // Value returned by the last finished call
+        let mut ret = None;
 
-        match node.inner {
-            NodeType::Empty => self.insert_empty_at(pos),
+        while let Some(call) = stack.pop() {
+            let Call {
+                len, current, seq, ..
+            } = call;
+            let node = self.base_graph.get(current);
+            let l1 = node.max_length;
+            let l2 = seq.len();
+            // Different nodes can have the same maximum length, the memo
+            // is keyed by the node itself
+            let pos = Position(len, current, l2, 0);
 
-            // Use the algorithm for two sequences
-            NodeType::End { suffix } => {
-                let (subgraph, start) = super::xmcs2_raw(len, suffix, seq);
-                self.insert_subgraph_at(pos, subgraph, start)
+            match call.step {
+                Step::Enter => (),
+                Step::Element => {
+                    if let NodeType::Element { value, .. } = node.inner {
+                        ret = self.compute_common_element_node(ret, value, pos)?;
+                    }
+                    continue;
+                }
+                Step::First => {
+                    // Second subgraph of a split node, or the element of a
+                    // mismatching element node matched later in `seq`
+                    let next = match node.inner {
+                        NodeType::Split { child2, .. } => child2,
+                        NodeType::Element { child, .. } => child,
+                        _ => unreachable!(),
+                    };
+                    stack.push(call.then(Step::Second(ret)));
+                    stack.push(Call::new(len, next, seq, 0));
+                    continue;
+                }
+                Step::Second(index1) => {
+                    ret = self.compute_split_node(index1, ret, pos)?;
+                    continue;
+                }
+                Step::Match | Step::Skip => unreachable!(),
             }
 
-            NodeType::Split { child1, child2 } => {
-                let index1 = self.compute(len, child1, seq);
-                let index2 = self.compute(len, child2, seq);
+            // Empty set, checked first as it is cheaper than the memo and
+            // keeps the positions stored in the memo in its band
+            if len > l1 || len > l2 {
+                ret = None;
+                continue;
+            }
 
-                self.compute_split_node(index1, index2, pos)
+            // Value already computed once, return its index in the array
+            if let Some(index) = self.memo.get(pos) {
+                ret = index;
+                continue;
             }
 
-            // `seq` is empty but we have enough elements: end here
-            NodeType::Element { .. } if l2 == 0 && len == 0 => {
-                let node = Node {
-                    max_length: 0,
-                    min_length: 0,
-                    inner: NodeType::End { suffix: seq },
-                };
+            monitor.step(|| self.usage())?;
 
-                self.insert_node_at(pos, node)
-            }
+            ret = match node.inner {
+                NodeType::Empty => self.insert_empty_at(pos),
+
+                // The algorithm for two sequences would stop at once,
+                // keeping the shorter sequence
+                NodeType::End { suffix } if self.is_subsequence(suffix, seq) => {
+                    let node = Node {
+                        max_length: min(l1, l2),
+                        min_length: min(l1, l2),
+                        inner: NodeType::End {
+                            suffix: if l1 < l2 { suffix } else { seq },
+                        },
+                    };
+
+                    self.insert_node_at(pos, node)?
+                }
+
+                // Use the algorithm for two sequences
+                NodeType::End { suffix } => {
+                    let substr = self.tables.get(len, suffix, seq);
+                    let usage = self.usage();
+                    let (subgraph, start, shared) =
+                        super::xmcs2_raw(len, suffix, seq, &substr, monitor, usage)?;
+                    self.shared += shared;
+                    self.insert_subgraph_at(pos, &subgraph, start)?
+                }
+
+                NodeType::Split { child1, .. } => {
+                    stack.push(call.then(Step::First));
+                    stack.push(Call::new(len, child1, seq, 0));
+                    continue;
+                }
+
+                // `seq` is empty but we have enough elements: end here
+                NodeType::Element { .. } if l2 == 0 && len == 0 => {
+                    let node = Node {
+                        max_length: 0,
+                        min_length: 0,
+                        inner: NodeType::End { suffix: seq },
+                    };
 
-            // `seq` is empty and not enough elements: empty set
-            NodeType::Element { .. } if l2 == 0 => self.insert_empty_at(pos),
+                    self.insert_node_at(pos, node)?
+                }
+
+                // `seq` is empty and not enough elements: empty set
+                NodeType::Element { .. } if l2 == 0 => self.insert_empty_at(pos),
+
+                // Matching elements (safety: `seq` is not empty here)
+                NodeType::Element { value, child } if value == seq[0] => {
+                    let len = len.saturating_sub(1); // Stop at 0
+                    stack.push(call.then(Step::Element));
+                    stack.push(Call::new(len, child, &seq[1..], 0));
+                    continue;
+                }
+
+                // Mismatching elements
+                NodeType::Element { .. } => {
+                    stack.push(call.then(Step::First));
+                    stack.push(Call::new(len, current, &seq[1..], 0));
+                    continue;
+                }
+            };
+        }
+
+        Ok(ret)
+    }
+
+    /// Returns whether `suffix` is a subsequence of `seq`, according to
+    /// the automaton of the sequence being added, or `false` if it was
+    /// not built
+    ///
+    /// `seq` must be a tail of the sequence being added.
+    fn is_subsequence(&self, suffix: &[T], seq: &[T]) -> bool {
+        self.automaton
+            .as_ref()
+            .is_some_and(|(automaton, is_subsequence_from)| {
+                is_subsequence_from(automaton, automaton.len() - seq.len(), suffix)
+            })
+    }
+}
+
+impl<'a, T> Builder<'a, T>
+where
+    T: Eq + Copy,
+{
+    /// Compute (or retrieve if already calculated) the part of the graph
+    /// representing the sequences of the subgraph starting at `current`
+    /// that can be found in `seq` with consecutive elements at most `gap`
+    /// positions apart
+    ///
+    /// `dist` is the distance between the last matched element and the
+    /// first element of `seq`, or 0 if no element was matched yet.
+    fn compute_gapped(
+        &mut self,
+        monitor: &mut Monitor<'_>,
+        len: usize,
+        current: usize,
+        seq: &'a [T],
+        gap: usize,
+        dist: usize,
+    ) -> Result<Option<usize>, Error> {
+        let mut stack = vec![Call::new(len, current, seq, dist)];
+        // Value returned by the last finished call
+        let mut ret = None;
+
+        while let Some(mut call) = stack.pop() {
+            let Call {
+                len,
+                current,
+                seq,
+                dist,
+                ..
+            } = call;
+            let node = self.base_graph.get(current);
+            let l2 = seq.len();
+            // Sequences of the base graph are not all maximal, the
+            // position must identify the node itself
+            let pos = Position(len, current, l2, dist);
+            let started = dist > 0;
+
+            match (call.step, &node.inner) {
+                (Step::Enter, _) => {
+                    // Empty set
+                    if len > node.max_length || len > l2 {
+                        ret = None;
+                        continue;
+                    }
+
+                    // Value already computed once, return its index in the array
+                    if let Some(index) = self.memo.get(pos) {
+                        ret = index;
+                        continue;
+                    }
+
+                    monitor.step(|| self.usage())?;
 
-            // Matching elements (safety: `seq` is not empty here)
-            NodeType::Element { value, child } if value == seq[0] => {
-                let len = len.saturating_sub(1); // Stop at 0
-                let index = self.compute(len, child, &seq[1..]);
+                    match node.inner {
+                        NodeType::Empty => call.res = None,
 
-                self.compute_common_element_node(index, value, pos)
+                        // End of the sequence, the gap-constrained graphs
+                        // only end with empty suffixes
+                        NodeType::End { suffix } => {
+                            debug_assert!(suffix.is_empty());
+                            call.res = Some(self.end_node()?);
+                        }
+
+                        NodeType::Split { child1, .. } => {
+                            stack.push(call.then(Step::First));
+                            stack.push(Call::new(len, child1, seq, dist));
+                            continue;
+                        }
+
+                        // Matching elements, if not too far from the
+                        // previous one
+                        NodeType::Element { value, child }
+                            if l2 > 0 && value == seq[0] && (!started || dist <= gap) =>
+                        {
+                            let len = len.saturating_sub(1);
+                            stack.push(call.then(Step::Match));
+                            stack.push(Call::new(len, child, &seq[1..], 1));
+                            continue;
+                        }
+
+                        NodeType::Element { .. } => (),
+                    }
+                }
+                (Step::First, &NodeType::Split { child2, .. }) => {
+                    stack.push(call.then(Step::Second(ret)));
+                    stack.push(Call::new(len, child2, seq, dist));
+                    continue;
+                }
+                (Step::Second(index1), _) => call.res = self.union(index1, ret)?,
+                (Step::Match, &NodeType::Element { value, .. }) => {
+                    call.res = ret.map(|i| self.push_element(value, i)).transpose()?;
+                }
+                (Step::Skip, _) => call.res = self.union(call.res, ret)?,
+                _ => unreachable!(),
             }
 
-            // Mismatching elements
-            NodeType::Element { child, .. } => {
-                let index1 = self.compute(len, current, &seq[1..]);
-                let index2 = self.compute(len, child, seq);
+            // Skip an element of `seq`, only if the element of the graph
+            // can still be matched after it
+            if let NodeType::Element { .. } = node.inner {
+                if matches!(call.step, Step::Enter | Step::Match)
+                    && l2 > 0
+                    && (!started || dist < gap)
+                {
+                    let d = if started { dist + 1 } else { 0 };
+                    stack.push(call.then(Step::Skip));
+                    stack.push(Call::new(len, current, &seq[1..], d));
+                    continue;
+                }
+            }
 
-                self.compute_split_node(index1, index2, pos)
+            self.memo.insert(pos, call.res);
+            ret = call.res;
+        }
+
+        Ok(ret)
+    }
+
+    /// Insert `node` into the graph without registering its position,
+    /// or find an existing node with the same content, and return its
+    /// index
+    ///
+    /// Fails with the `compact` feature if the graph is full.
+    fn push_node(&mut self, node: Node<'a, T>) -> Result<usize, Error> {
+        self.interner
+            .insert(&mut self.nodes, node)
+            .ok_or_else(|| self.usage().exceeded(self.sequence))
+    }
+
+    /// Returns the index of the node representing the empty sequence,
+    /// inserting it if needed.
+    fn end_node(&mut self) -> Result<usize, Error> {
+        self.push_node(Node {
+            max_length: 0,
+            min_length: 0,
+            inner: NodeType::End { suffix: &[] },
+        })
+    }
+
+    /// Insert an element node pointing to `child` and returns its index
+    fn push_element(&mut self, value: T, child: usize) -> Result<usize, Error> {
+        let node = self.nodes.get(child);
+        self.push_node(Node {
+            max_length: node.max_length + 1,
+            min_length: node.min_length + 1,
+            inner: NodeType::Element { value, child },
+        })
+    }
+
+    /// Returns the index of a node representing the union of two
+    /// subgraphs, inserting a split node if needed.
+    fn union(
+        &mut self,
+        index1: Option<usize>,
+        index2: Option<usize>,
+    ) -> Result<Option<usize>, Error> {
+        match (index1, index2) {
+            (None, None) => Ok(None),
+            (Some(i), None) | (None, Some(i)) => Ok(Some(i)),
+            (Some(i1), Some(i2)) if i1 == i2 => Ok(Some(i1)),
+            (Some(i1), Some(i2)) => {
+                let node1 = self.nodes.get(i1);
+                let node2 = self.nodes.get(i2);
+
+                self.push_node(Node {
+                    max_length: max(node1.max_length, node2.max_length),
+                    min_length: min(node1.min_length, node2.min_length),
+                    inner: NodeType::Split {
+                        child1: i1,
+                        child2: i2,
+                    },
+                })
+                .map(Some)
             }
         }
     }
 
-    // Insert `node` into the graph and return its index
-    fn insert_node_at(&mut self, position: Position, node: Node<'a, T>) -> Option<usize> {
-        let index = Some(self.nodes.len());
-        self.nodes.push(node);
+    // Insert `node` into the graph, or find an existing node with the
+    // same content, and return its index
+    fn insert_node_at(
+        &mut self,
+        position: Position,
+        node: Node<'a, T>,
+    ) -> Result<Option<usize>, Error> {
+        let index = Some(self.push_node(node)?);
         self.memo.insert(position, index);
-        index
+        Ok(index)
     }
 
     /// Register that a position points to an existing node
     ///
     /// Panics if that node does not exists
-    fn points_to_node(&mut self, position: Position, index: usize) -> Option<usize> {
+    fn points_to_node(&mut self, position: Position, index: usize) -> usize {
         assert!(index < self.nodes.len());
         self.memo.insert(position, Some(index));
-        Some(index)
+        index
     }
 
     /// Register that a node is empty
@@ -149,24 +589,28 @@ where
         None
     }
 
-    /// Insert another graph into `self`, shifting all the
-    /// indices to keep correct references to children
+    /// Insert another graph into `self`, sharing its nodes with
+    /// the existing ones and updating the references to children,
     /// return the index of the first node of the inserted
     /// subgraph or `None` if the subgraph was empty.
+    ///
+    /// The children of the nodes of `other` must come before them.
     #[inline(always)]
     fn insert_subgraph_at(
         &mut self,
         position: Position,
-        other: Vec<Node<'a, T>>,
+        other: &Nodes<'a, T>,
         start: Option<usize>,
-    ) -> Option<usize> {
+    ) -> Result<Option<usize>, Error> {
         match start {
-            None => self.insert_empty_at(position),
+            None => Ok(self.insert_empty_at(position)),
             Some(start) => {
-                let index = self.nodes.len();
-                let nodes = other.into_iter().map(|node| node.with_base_index(index));
-                self.nodes.extend(nodes);
-                Some(start + index)
+                let mut indices = Vec::with_capacity(other.len());
+                for node in other.iter() {
+                    let node = node.with_indices(|i| indices[i]);
+                    indices.push(self.push_node(node)?);
+                }
+                Ok(Some(self.points_to_node(position, indices[start])))
             }
         }
     }
@@ -177,22 +621,24 @@ where
         index1: Option<usize>,
         index2: Option<usize>,
         position: Position,
-    ) -> Option<usize> {
+    ) -> Result<Option<usize>, Error> {
         match (index1, index2) {
             // Both children empty => node is empty
-            (None, None) => self.insert_empty_at(position),
+            (None, None) => Ok(self.insert_empty_at(position)),
 
             // Only one child non-empty => node is equal to that child
-            (Some(idx), None) | (None, Some(idx)) => self.points_to_node(position, idx),
+            (Some(idx), None) | (None, Some(idx)) => Ok(Some(self.points_to_node(position, idx))),
 
             // (Optimization)
             // The children are identical => point to that node
-            (Some(idx1), Some(idx2)) if idx1 == idx2 => self.points_to_node(position, idx1),
+            (Some(idx1), Some(idx2)) if idx1 == idx2 => {
+                Ok(Some(self.points_to_node(position, idx1)))
+            }
 
             // Else the node is a split node
             (Some(idx1), Some(idx2)) => {
-                let node1 = &self.nodes[idx1];
-                let node2 = &self.nodes[idx2];
+                let node1 = self.nodes.get(idx1);
+                let node2 = self.nodes.get(idx2);
 
                 // (Optimization)
                 // If we are in a case like this:
@@ -203,10 +649,10 @@ where
                 // Then node 1 has the same children, we can use it
                 // instead of inserting a new node.
                 if node1.is_split_with_child(idx2) {
-                    return self.points_to_node(position, idx1);
+                    return Ok(Some(self.points_to_node(position, idx1)));
                 }
                 if node2.is_split_with_child(idx1) {
-                    return self.points_to_node(position, idx2);
+                    return Ok(Some(self.points_to_node(position, idx2)));
                 }
 
                 let max_length = max(node1.max_length, node2.max_length);
@@ -232,11 +678,11 @@ where
         index: Option<usize>,
         element: T,
         position: Position,
-    ) -> Option<usize> {
+    ) -> Result<Option<usize>, Error> {
         match index {
-            None => self.insert_empty_at(position),
+            None => Ok(self.insert_empty_at(position)),
             Some(idx) => {
-                let node = &self.nodes[idx];
+                let node = self.nodes.get(idx);
 
                 let max_length = node.max_length + 1;
                 let min_length = node.min_length + 1;
@@ -256,21 +702,576 @@ where
     }
 }
 
-/// Compute an extend set of maximal common subsequences of 
+/// Compute an extend set of maximal common subsequences of
 /// length at least `l` of the sequences in `sequences`,
 /// represented as an acyclic automata.
+///
+/// # Panics
+/// With the `compact` feature, panics if the graph has more than `2^31`
+/// nodes.
 pub fn xmcsk<'a, T>(len: usize, sequences: &[&'a [T]]) -> Dag<'a, T>
 where
     T: Eq + Copy,
 {
-    match sequences {
-        &[] => Dag::empty(len),
-        &[s] => Dag::singleton(len, s),
-        // In theory this case is not necessary
-        //&[s1, s2] => super::xmcs2(len, s1, s2),
-        &[ref seqs @ .., s] => {
-            let graph = xmcsk(len, seqs);
-            Builder::add_sequence(graph, s)
+    let mut monitor = Monitor::unlimited(sequences.len());
+    match build(len, sequences, Builder::add_sequence, &mut monitor) {
+        Ok(dag) => dag,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+impl<'a, T> Dag<'a, T>
+where
+    T: Eq + Hash + Copy,
+{
+    /// Same as [`xmcsk`], but uses the automaton of each sequence added
+    /// to the graph to keep the End nodes whose suffix is a subsequence
+    /// of the rest of the sequence without expanding them.
+    ///
+    /// # Panics
+    /// Same as [`xmcsk`].
+    pub(crate) fn xmcsk_with_automaton(len: usize, sequences: &[&'a [T]]) -> Self {
+        let mut monitor = Monitor::unlimited(sequences.len());
+        match build(len, sequences, Builder::add_sequence_with_automaton, &mut monitor) {
+            Ok(dag) => dag,
+            Err(e) => panic!("{}", e),
         }
     }
+
+    /// Same as [`xmcsk_with_automaton`](Dag::xmcsk_with_automaton), but
+    /// returns an error if the parameters are invalid.
+    ///
+    /// # Errors
+    /// Same as [`try_xmcsk`].
+    pub(crate) fn try_xmcsk_with_automaton(
+        len: usize,
+        sequences: &[&'a [T]],
+    ) -> Result<Self, Error> {
+        check_length(len, sequences)?;
+        check_upper_bound(len, sequences)?;
+        let mut monitor = Monitor::unlimited(sequences.len());
+        build(len, sequences, Builder::add_sequence_with_automaton, &mut monitor)
+    }
+}
+
+/// Add the sequences one by one with `add`, reporting the resources used
+/// to `monitor`
+fn build<'a, T>(
+    len: usize,
+    sequences: &[&'a [T]],
+    add: AddSequence<'a, T>,
+    monitor: &mut Monitor<'_>,
+) -> Result<Dag<'a, T>, Error>
+where
+    T: Eq + Copy,
+{
+    let Some((first, sequences)) = sequences.split_first() else {
+        return Ok(Dag::empty(len));
+    };
+
+    let mut graph = Dag::singleton(len, first);
+    monitor.added(Usage {
+        nodes: graph.node_count(),
+        ..Usage::default()
+    })?;
+
+    // In theory the algorithm for two sequences could be used for the
+    // second one, but adding it to the singleton is equivalent
+    for s in sequences {
+        monitor.sequence += 1;
+        graph = add(graph, s, monitor)?;
+    }
+
+    Ok(graph)
+}
+
+/// Same as [`xmcsk`], but returns an error if the parameters are invalid.
+///
+/// # Errors
+/// Returns [`Error::LengthTooLarge`] if `len` is larger than the length
+/// of one of the sequences, or than the bound on the length of a longest
+/// common subsequence of
+/// [`check_upper_bound`](crate::bounds::check_upper_bound). With the
+/// `compact` feature, returns [`Error::BudgetExceeded`] if the graph has
+/// more than `2^31` nodes.
+pub fn try_xmcsk<'a, T>(len: usize, sequences: &[&'a [T]]) -> Result<Dag<'a, T>, Error>
+where
+    T: Eq + Copy,
+{
+    check_length(len, sequences)?;
+    check_upper_bound(len, sequences)?;
+    let mut monitor = Monitor::unlimited(sequences.len());
+    build(len, sequences, Builder::add_sequence, &mut monitor)
+}
+
+/// Same as [`try_xmcsk`], but stops with an error if building the graph
+/// uses more resources than allowed by `budget`.
+///
+/// # Errors
+/// Same as [`try_xmcsk`], and returns [`Error::BudgetExceeded`] if one
+/// of the limits of `budget` is exceeded.
+pub fn try_xmcsk_with_budget<'a, T>(
+    len: usize,
+    sequences: &[&'a [T]],
+    budget: &Budget,
+) -> Result<Dag<'a, T>, Error>
+where
+    T: Eq + Copy,
+{
+    check_length(len, sequences)?;
+    check_upper_bound(len, sequences)?;
+    let mut monitor = Monitor::new(*budget, None, sequences.len());
+    build(len, sequences, Builder::add_sequence, &mut monitor)
+}
+
+/// Same as [`try_xmcsk_with_budget`], but also reports the progress of
+/// the computation to `observer`, which can cancel it.
+///
+/// The observer is notified after each sequence is added to the graph,
+/// and periodically while a sequence is being added.
+///
+/// # Errors
+/// Same as [`try_xmcsk_with_budget`], and returns [`Error::Cancelled`]
+/// if the observer cancels the computation.
+pub fn try_xmcsk_observed<'a, T>(
+    len: usize,
+    sequences: &[&'a [T]],
+    budget: &Budget,
+    observer: &mut dyn Observer,
+) -> Result<Dag<'a, T>, Error>
+where
+    T: Eq + Copy,
+{
+    check_length(len, sequences)?;
+    check_upper_bound(len, sequences)?;
+    let mut monitor = Monitor::new(*budget, Some(observer), sequences.len());
+    build(len, sequences, Builder::add_sequence, &mut monitor)
+}
+
+/// Compute a graph representing the set of gap-constrained common
+/// subsequences of the sequences in `sequences`.
+///
+/// The graph contains the common subsequences of length at least `len`
+/// whose consecutive elements are at most `gap` positions apart in all
+/// the sequences: `gap = 1` only keeps contiguous subsequences, and
+/// `gap = 0` only the subsequences of at most one element. A single
+/// sequence is compared with itself, so the graph holds all its
+/// subsequences satisfying the constraint.
+///
+/// See [`xmcs2_with_gap`](super::xmcs2_with_gap).
+///
+/// # Panics
+/// Same as [`xmcs2_with_gap`](super::xmcs2_with_gap).
+pub fn xmcsk_with_gap<'a, T>(len: usize, gap: usize, sequences: &[&'a [T]]) -> Dag<'a, T>
+where
+    T: Eq + Copy,
+{
+    match build_gapped(len, gap, sequences, &mut Monitor::unlimited(sequences.len())) {
+        Ok(dag) => dag,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Compute the graph of the first two sequences, then add the others one
+/// by one, reporting the resources used to `monitor`
+fn build_gapped<'a, T>(
+    len: usize,
+    gap: usize,
+    sequences: &[&'a [T]],
+    monitor: &mut Monitor<'_>,
+) -> Result<Dag<'a, T>, Error>
+where
+    T: Eq + Copy,
+{
+    let (mut graph, sequences) = match *sequences {
+        [] => return Ok(Dag::empty(len)),
+        // The sequence itself only satisfies the constraint if its
+        // elements are close enough: compare it with itself instead
+        [s] => {
+            let graph = super::xmcs2_with_gap_monitored(len, gap, s, s, monitor)?;
+            (graph, &[][..])
+        }
+        [s1, s2, ref sequences @ ..] => {
+            monitor.sequence = 1;
+            let graph = super::xmcs2_with_gap_monitored(len, gap, s1, s2, monitor)?;
+            (graph, sequences)
+        }
+    };
+    monitor.added(Usage {
+        nodes: graph.node_count(),
+        ..Usage::default()
+    })?;
+
+    for s in sequences {
+        monitor.sequence += 1;
+        graph = Builder::add_sequence_gapped(graph, gap, s, monitor)?;
+    }
+
+    Ok(graph)
+}
+
+/// Same as [`xmcsk_with_gap`], but returns an error if the parameters are
+/// invalid.
+///
+/// # Errors
+/// Same as [`try_xmcs2_with_gap`](super::try_xmcs2_with_gap).
+pub fn try_xmcsk_with_gap<'a, T>(
+    len: usize,
+    gap: usize,
+    sequences: &[&'a [T]],
+) -> Result<Dag<'a, T>, Error>
+where
+    T: Eq + Copy,
+{
+    check_length(len, sequences)?;
+    check_gap(gap, sequences)?;
+    check_upper_bound(len, sequences)?;
+    build_gapped(len, gap, sequences, &mut Monitor::unlimited(sequences.len()))
+}
+
+/// Same as [`try_xmcsk_with_gap`], but with a budget and an observer.
+///
+/// Stops with an error if building the graph uses more resources than
+/// allowed by `budget`, and reports the progress of the computation to
+/// `observer`, which can cancel it.
+///
+/// # Errors
+/// Same as [`try_xmcsk_observed`], and returns
+/// [`Error::GapTooLarge`] as [`try_xmcsk_with_gap`] does.
+pub fn try_xmcsk_with_gap_observed<'a, T>(
+    len: usize,
+    gap: usize,
+    sequences: &[&'a [T]],
+    budget: &Budget,
+    observer: &mut dyn Observer,
+) -> Result<Dag<'a, T>, Error>
+where
+    T: Eq + Copy,
+{
+    check_length(len, sequences)?;
+    check_gap(gap, sequences)?;
+    check_upper_bound(len, sequences)?;
+    let mut monitor = Monitor::new(*budget, Some(observer), sequences.len());
+    build_gapped(len, gap, sequences, &mut monitor)
+}
+
+// === Tests ===
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dag::{Control, Progress};
+    use crate::reference::random_sequences;
+
+    #[test]
+    fn test_same_max_length() {
+        // Nodes of the graph of the first two sequences with the same
+        // maximum length lead to different subsequences
+        let dag = xmcsk(1, &[b"DDAB", b"BBDBAD", b"AA"]);
+        assert_eq!(Some(b"A".to_vec()), dag.extract_lcs());
+    }
+
+    /// Returns whether `sub` can be found in `seq` with consecutive
+    /// elements at most `gap` positions apart (brute force)
+    fn is_gap_subsequence(sub: &[u8], seq: &[u8], gap: usize, last: Option<usize>) -> bool {
+        match sub.split_first() {
+            None => true,
+            Some((&e, rest)) => (0..seq.len())
+                .filter(|&p| last.is_none_or(|l| p > l && p - l <= gap))
+                .any(|p| seq[p] == e && is_gap_subsequence(rest, seq, gap, Some(p))),
+        }
+    }
+
+    #[test]
+    fn test_xmcsk_with_gap() {
+        for seqs in random_sequences(100, 0x2545_f491_4f6c_dd1d, 2..=4, 1..=8, 3) {
+            let seqs = seqs.iter().map(Vec::as_slice).collect::<Vec<_>>();
+
+            for gap in 1..=3 {
+                let is_valid =
+                    |sub: &[u8]| seqs.iter().all(|s| is_gap_subsequence(sub, s, gap, None));
+
+                // Longest valid subsequence, by enumerating subsequences of
+                // the first sequence
+                let first = seqs[0];
+                let best = (0..1_u32 << first.len())
+                    .map(|mask| {
+                        (0..first.len())
+                            .filter(|i| mask >> i & 1 == 1)
+                            .map(|i| first[i])
+                            .collect::<Vec<_>>()
+                    })
+                    .filter(|sub| is_valid(sub))
+                    .map(|sub| sub.len())
+                    .max()
+                    .unwrap_or(0);
+
+                for len in 0..=2 {
+                    let dag = xmcsk_with_gap(len, gap, &seqs);
+                    let lcs = dag.extract_lcs();
+
+                    if best >= len && best > 0 {
+                        assert_eq!(Some(best), lcs.map(|s| s.len()), "{seqs:?}");
+                    } else {
+                        assert_eq!(None, lcs);
+                    }
+
+                    for sub in dag.to_set() {
+                        assert!(sub.len() >= len);
+                        assert!(is_valid(&sub), "{:?} {:?}", seqs, sub);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_single_sequence_with_gap() {
+        let s = b"ABCD";
+        let dag = xmcsk_with_gap(4, 1, &[s]);
+        assert_eq!(Some(s.to_vec()), dag.extract_lcs());
+
+        // Too short for the minimum length
+        let dag = xmcsk_with_gap(5, 1, &[s]);
+        assert_eq!(None, dag.extract_lcs());
+        assert_eq!(
+            Err(Error::LengthTooLarge { len: 5, max: 4 }),
+            try_xmcsk_with_gap(5, 1, &[s]).map(|d| d.extract_lcs())
+        );
+
+        // Same as the sequence compared with itself, whatever the gap
+        let s = b"ABACBD";
+        for gap in 0..=3 {
+            for len in 0..=3 {
+                let expected = xmcsk_with_gap(len, gap, &[s, s]).to_set();
+                assert_eq!(expected, xmcsk_with_gap(len, gap, &[s]).to_set());
+            }
+        }
+        let lcs = xmcsk_with_gap(1, 0, &[s]).extract_lcs();
+        assert_eq!(Some(1), lcs.map(|s| s.len()));
+        assert_eq!(Some(b"ABA".to_vec()), xmcsk_with_gap(2, 1, &[b"ABA"]).extract_lcs());
+    }
+
+    #[test]
+    fn test_budget() {
+        let seqs = [&b"ADBCBAD"[..], b"ADCBACD", b"ABDCABDA", b"BADCDBAC"];
+        let expected = xmcsk(2, &seqs);
+
+        let dag = try_xmcsk_with_budget(2, &seqs, &Budget::default()).unwrap();
+        assert_eq!(expected.to_set(), dag.to_set());
+
+        let budget = Budget {
+            max_nodes: Some(expected.node_count()),
+            max_memo_entries: Some(1000),
+            max_bytes: Some(1 << 20),
+        };
+        let dag = try_xmcsk_with_budget(2, &seqs, &budget).unwrap();
+        assert_eq!(expected.to_set(), dag.to_set());
+
+        // Each limit stops the computation
+        let budgets = [
+            Budget {
+                max_nodes: Some(expected.node_count() - 1),
+                ..Budget::default()
+            },
+            Budget {
+                max_memo_entries: Some(3),
+                ..Budget::default()
+            },
+            Budget {
+                max_bytes: Some(64),
+                ..Budget::default()
+            },
+        ];
+        for budget in &budgets {
+            match try_xmcsk_with_budget(2, &seqs, budget) {
+                Err(Error::BudgetExceeded { sequence, .. }) => assert!(sequence < seqs.len()),
+                res => panic!("{:?}", res.map(|dag| dag.node_count())),
+            }
+        }
+
+        // The last sequence needs the most nodes
+        let budget = Budget {
+            max_nodes: Some(expected.node_count() - 1),
+            ..Budget::default()
+        };
+        assert_eq!(
+            Err(3),
+            try_xmcsk_with_budget(2, &seqs, &budget)
+                .map(|_| ())
+                .map_err(|e| match e {
+                    Error::BudgetExceeded { sequence, .. } => sequence,
+                    _ => 0,
+                })
+        );
+    }
+
+    #[cfg(feature = "compact")]
+    #[test]
+    fn test_compact_budget() {
+        use crate::dag::nodes::MAX_NODES;
+
+        // The number of nodes is always limited by their storage
+        assert_eq!(Some(MAX_NODES), Budget::default().capped().max_nodes);
+        let budget = Budget {
+            max_nodes: Some(usize::MAX),
+            ..Budget::default()
+        };
+        assert_eq!(Some(MAX_NODES), budget.capped().max_nodes);
+        let budget = Budget {
+            max_nodes: Some(10),
+            ..Budget::default()
+        };
+        assert_eq!(budget, budget.capped());
+    }
+
+    #[test]
+    fn test_observer() {
+        let seqs = [&b"ADBCBAD"[..], b"ADCBACD", b"ABDCABDA", b"BADCDBAC"];
+
+        let mut added = Vec::new();
+        let mut observer = |p: &Progress| {
+            assert_eq!(4, p.sequences);
+            if p.added {
+                added.push(p.sequence);
+            }
+            if p.sequence == 2 {
+                Control::Cancel
+            } else {
+                Control::Continue
+            }
+        };
+        assert_eq!(
+            Some(Error::Cancelled { sequence: 2 }),
+            try_xmcsk_observed(2, &seqs, &Budget::default(), &mut observer).err()
+        );
+        assert_eq!(vec![0, 1, 2], added);
+
+        // Periodic notifications while adding a long sequence
+        let n = 3000;
+        let s1 = (0..n).map(|i| b"ACGT"[i * 7 % 11 % 4]).collect::<Vec<_>>();
+        let mut s2 = s1.clone();
+        for i in (0..n).step_by(100) {
+            s2[i] = b'A';
+        }
+        let len = crate::lcs::lcs_length(&s1, &s2) - 10;
+
+        let mut periodic = Vec::new();
+        let mut observer = |p: &Progress| {
+            if !p.added {
+                periodic.push(p.memo_entries);
+            }
+            Control::Continue
+        };
+        let dag = try_xmcsk_observed(len, &[&s1, &s2], &Budget::default(), &mut observer).unwrap();
+        assert_eq!(Some(len + 10), dag.extract_lcs().map(|s| s.len()));
+        assert!(!periodic.is_empty());
+        assert!(periodic.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_observer_with_gap() {
+        let seqs = [&b"ADBCBAD"[..], b"ADCBACD", b"ABDCABDA", b"BADCDBAC"];
+        let expected = xmcsk_with_gap(2, 2, &seqs);
+
+        // The first two sequences are added at once
+        let mut added = Vec::new();
+        let mut observer = |p: &Progress| {
+            if p.added {
+                added.push(p.sequence);
+            }
+            Control::Continue
+        };
+        let dag =
+            try_xmcsk_with_gap_observed(2, 2, &seqs, &Budget::default(), &mut observer).unwrap();
+        assert_eq!(expected.to_set(), dag.to_set());
+        assert_eq!(vec![1, 2, 3], added);
+
+        let budget = Budget {
+            max_nodes: Some(expected.node_count() - 1),
+            ..Budget::default()
+        };
+        let mut observer = |_: &Progress| Control::Continue;
+        assert!(matches!(
+            try_xmcsk_with_gap_observed(2, 2, &seqs, &budget, &mut observer),
+            Err(Error::BudgetExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn test_automaton() {
+        // Without the automaton, every End node is expanded with the
+        // algorithm for two sequences
+        for seqs in random_sequences(100, 0x9e37_79b9_7f4a_7c15, 2..=4, 0..=10, 3) {
+            let seqs = seqs.iter().map(Vec::as_slice).collect::<Vec<_>>();
+            let shortest = seqs.iter().map(|s| s.len()).min().unwrap_or(0);
+
+            for len in 1..=min(shortest, 4) {
+                let expected = xmcsk(len, &seqs);
+                let dag = Dag::xmcsk_with_automaton(len, &seqs);
+                assert_eq!(expected.to_set(), dag.to_set(), "{seqs:?}");
+                assert!(crate::set::xmcsk(len, &seqs).is_subset(&dag.to_set()));
+                assert_eq!(
+                    try_xmcsk(len, &seqs).map(|d| d.to_set()),
+                    Dag::try_xmcsk_with_automaton(len, &seqs).map(|d| d.to_set())
+                );
+            }
+        }
+
+        // One symbol per position, larger than the limit
+        let s1 = (0..3000_u32).collect::<Vec<_>>();
+        let mut s2 = s1.clone();
+        s2.remove(1000);
+        let mut s3 = s1.clone();
+        s3.remove(2000);
+        assert!(Automaton::with_limit(&s1, AUTOMATON_LIMIT).is_none());
+        let dag = Dag::xmcsk_with_automaton(2997, &[&s1, &s2, &s3]);
+        assert_eq!(Some(2998), dag.extract_lcs().map(|s| s.len()));
+    }
+
+    #[test]
+    fn test_shared_nodes() {
+        let seqs = [&b"ADBCBAD"[..], b"ADCBACD", b"ABDCABDA", b"BADCDBAC"];
+        let dag = xmcsk(2, &seqs);
+        assert!(dag.shared_count() > 0);
+
+        // No two nodes have the same content
+        let mut contents = std::collections::HashSet::new();
+        for node in dag.nodes.iter() {
+            let content = match node.inner {
+                NodeType::Empty => (0, 0, 0),
+                NodeType::End { suffix } => (1, suffix.as_ptr() as usize, suffix.len()),
+                NodeType::Split { child1, child2 } => (2, child1, child2),
+                NodeType::Element { value, child } => (3, child, usize::from(value)),
+            };
+            assert!(contents.insert(content), "{:?}", node);
+        }
+    }
+
+    #[test]
+    fn test_long_sequences() {
+        // Far more elements than calls fitting in the stack of a test
+        // thread if each element needed a recursive call
+        let n = 200_000;
+        let s1 = (0..n).map(|i| b"ACGT"[i * 7 % 11 % 4]).collect::<Vec<_>>();
+        let mut s2 = s1.clone();
+        s2.remove(n / 2);
+        s2.remove(n / 3);
+        let mut s3 = s1.clone();
+        s3.remove(n / 4);
+
+        let dag = super::super::xmcs2(n - 3, &s1, &s2);
+        assert_eq!(Some(&s2), dag.extract_lcs().as_ref());
+        assert_eq!(1, dag.to_set().len());
+        assert_eq!(
+            Some(n - 2),
+            dag.constrain(b"GT").extract_lcs().map(|s| s.len())
+        );
+
+        let dag = xmcsk(n - 4, &[&s1, &s3, &s2]);
+        assert_eq!(Some(n - 3), dag.extract_lcs().map(|s| s.len()));
+
+        let dag = xmcsk_with_gap(n - 3, 2, &[&s1, &s2]);
+        assert_eq!(Some(&s2), dag.extract_lcs().as_ref());
+    }
 }
@@ -0,0 +1,137 @@
+//! Error type returned by the fallible functions of this crate
+//!
+//! The functions computing sets of subsequences panic when their
+//! parameters are invalid. Each of them has a `try_` variant checking
+//! its parameters beforehand and returning an [`Error`] instead.
+
+use std::error;
+use std::fmt;
+
+/// Error returned when the parameters of a computation are invalid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The lengths of the two sequences differ by more than `delta`
+    LengthDifference {
+        /// Length of the first sequence
+        len1: usize,
+        /// Length of the second sequence
+        len2: usize,
+        /// Maximum difference allowed
+        delta: usize,
+    },
+    /// The minimum length of the subsequences is larger than the length
+    /// of one of the sequences, or than an upper bound on the length of a
+    /// longest common subsequence (see
+    /// [`check_upper_bound`](crate::bounds::check_upper_bound)), so no
+    /// common subsequence can be found
+    LengthTooLarge {
+        /// Minimum length requested
+        len: usize,
+        /// Maximum possible length of a common subsequence
+        max: usize,
+    },
+    /// An index is larger than the length of its sequence
+    IndexOutOfBounds {
+        /// Index in the first sequence
+        i: usize,
+        /// Index in the second sequence
+        j: usize,
+    },
+    /// The distance between two indices is larger than `delta`
+    IndexOutOfBand {
+        /// Index in the first sequence
+        i: usize,
+        /// Index in the second sequence
+        j: usize,
+        /// Maximum distance allowed
+        delta: usize,
+    },
+    /// The sequences have more distinct elements than the symbol type
+    /// used to encode them can represent
+    AlphabetTooLarge {
+        /// Number of distinct elements
+        symbols: usize,
+        /// Maximum number of symbols of the encoding
+        max: usize,
+    },
+    /// The computation was stopped because it exceeded its
+    /// [`Budget`](crate::dag::Budget)
+    BudgetExceeded {
+        /// Index of the sequence being added to the graph
+        sequence: usize,
+        /// Number of nodes of the graph being built
+        nodes: usize,
+        /// Number of positions stored in the memoisation tables
+        memo_entries: usize,
+        /// Estimated number of bytes allocated
+        bytes: usize,
+    },
+    /// The computation was cancelled by its
+    /// [`Observer`](crate::dag::Observer)
+    Cancelled {
+        /// Index of the sequence being added to the graph
+        sequence: usize,
+    },
+    /// The gap of the gap-constrained mode and one of the sequences are
+    /// longer than `max`, the pairs of distances would not fit in a
+    /// `usize`
+    GapTooLarge {
+        /// Gap requested
+        gap: usize,
+        /// Maximum gap supported for sequences longer than it
+        max: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::LengthDifference { len1, len2, delta } => write!(
+                f,
+                "sequences of lengths {len1} and {len2} differ by more than {delta}"
+            ),
+            Self::LengthTooLarge { len, max } => write!(
+                f,
+                "minimum length {len} is larger than the longest possible subsequence ({max})"
+            ),
+            Self::IndexOutOfBounds { i, j } => {
+                write!(f, "indices ({i}, {j}) are out of bounds")
+            }
+            Self::IndexOutOfBand { i, j, delta } => write!(
+                f,
+                "indices ({i}, {j}) are more than {delta} positions apart"
+            ),
+            Self::AlphabetTooLarge { symbols, max } => write!(
+                f,
+                "alphabet of {symbols} symbols cannot be encoded with at most {max} symbols"
+            ),
+            Self::BudgetExceeded {
+                sequence,
+                nodes,
+                memo_entries,
+                bytes,
+            } => write!(
+                f,
+                "budget exceeded while adding sequence {sequence}: {nodes} nodes, {memo_entries} memo entries, about {bytes} bytes"
+            ),
+            Self::Cancelled { sequence } => {
+                write!(f, "cancelled while adding sequence {sequence}")
+            }
+            Self::GapTooLarge { gap, max } => write!(
+                f,
+                "gap {gap} is larger than the maximum of {max} of the gap-constrained mode"
+            ),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// Check that all the sequences are at least `len` long
+pub(crate) fn check_length<T>(len: usize, seqs: &[&[T]]) -> Result<(), Error> {
+    match seqs.iter().map(|s| s.len()).min() {
+        Some(max) if max < len => Err(Error::LengthTooLarge { len, max }),
+        _ => Ok(()),
+    }
+}
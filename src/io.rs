@@ -0,0 +1,427 @@
+//! Read sequences from FASTA and FASTQ files
+//!
+//! This module contains functions to parse records from files in the
+//! FASTA or FASTQ formats into byte sequences that can be given directly
+//! to [`dag::xmcsk`]. The name of each record is kept so that results can
+//! be reported per input.
+//!
+//! Sequences can span multiple lines in both formats. Lowercase letters,
+//! used to denote soft-masked regions, are either kept as-is or converted
+//! to uppercase depending on the [`SoftMask`] parameter.
+//!
+//! # Examples
+//! ```
+//! use xmcs::io::{read_fasta, sequences, SoftMask};
+//!
+//! let file = b">seq1 first sequence\nACGT\nacGT\n>seq2\nAGGTACGT\n";
+//! let records = read_fasta(&file[..], SoftMask::Unmask).unwrap();
+//!
+//! assert_eq!("seq1", records[0].name);
+//! assert_eq!(b"ACGTACGT", &records[0].seq[..]);
+//!
+//! let dag = xmcs::dag::xmcsk(6, &sequences(&records));
+//! assert_eq!(Some(b"AGTACGT".to_vec()), dag.extract_lcs());
+//! ```
+//!
+//! [`dag::xmcsk`]: `crate::dag::xmcsk`
+
+use std::error;
+use std::fmt;
+use std::io::BufRead;
+
+/// A sequence read from a FASTA or FASTQ file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    /// Name of the record (first word of the header line)
+    pub name: String,
+    /// Rest of the header line, if any
+    pub description: Option<String>,
+    /// Sequence of the record
+    pub seq: Vec<u8>,
+    /// Quality string, only present for FASTQ records
+    pub quality: Option<Vec<u8>>,
+}
+
+/// What to do with lowercase (soft-masked) letters in a sequence
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoftMask {
+    /// Keep lowercase letters, they will not match their uppercase
+    /// counterpart
+    Keep,
+    /// Convert lowercase letters to uppercase
+    Unmask,
+}
+
+/// Error returned when a file cannot be parsed
+#[derive(Debug)]
+pub struct ParseError {
+    line: usize,
+    kind: ErrorKind,
+}
+
+/// Kind of error that can happen while parsing a file
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Error while reading the underlying reader
+    Io(std::io::Error),
+    /// Data found before the first header line
+    MissingHeader,
+    /// The header line does not contain a name
+    EmptyName,
+    /// A sequence contains a character that is not a letter,
+    /// `*` or `-`
+    InvalidCharacter(u8),
+    /// A FASTQ record has no `+` separator line
+    MissingSeparator,
+    /// The quality string of a FASTQ record is not as long as its sequence
+    QualityLength {
+        /// Length of the sequence
+        expected: usize,
+        /// Length of the quality string
+        found: usize,
+    },
+}
+
+impl ParseError {
+    /// Line (starting at 1) where the error was found
+    pub const fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Kind of error
+    pub const fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    const fn new(line: usize, kind: ErrorKind) -> Self {
+        Self { line, kind }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            ErrorKind::Io(e) => write!(f, "{e}"),
+            ErrorKind::MissingHeader => write!(f, "sequence data before the first header"),
+            ErrorKind::EmptyName => write!(f, "header without a record name"),
+            ErrorKind::InvalidCharacter(c) => {
+                write!(f, "invalid character {:?} in sequence", char::from(*c))
+            }
+            ErrorKind::MissingSeparator => write!(f, "missing `+` separator line"),
+            ErrorKind::QualityLength { expected, found } => write!(
+                f,
+                "quality string has length {found} but sequence has length {expected}"
+            ),
+        }
+    }
+}
+
+impl error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match &self.kind {
+            ErrorKind::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Read all the records of a FASTA file
+///
+/// Empty lines and lines starting with `;` are ignored, and trailing
+/// whitespace is removed from each line.
+///
+/// # Errors
+/// Returns an error if the reader fails, or if the content is not valid
+/// FASTA.
+pub fn read_fasta(reader: impl BufRead, mask: SoftMask) -> Result<Vec<Record>, ParseError> {
+    fasta(Lines::new(reader, 0), mask)
+}
+
+fn fasta(mut lines: Lines<impl BufRead>, mask: SoftMask) -> Result<Vec<Record>, ParseError> {
+    let mut records = Vec::new();
+
+    while let Some(line) = lines.next()? {
+        if line.is_empty() || line.starts_with(b";") {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix(b">") {
+            let record = new_record(header, lines.line)?;
+            records.push(record);
+        } else if let Some(record) = records.last_mut() {
+            push_sequence(&mut record.seq, &line, mask, lines.line)?;
+        } else {
+            return Err(ParseError::new(lines.line, ErrorKind::MissingHeader));
+        }
+    }
+
+    Ok(records)
+}
+
+/// Read all the records of a FASTQ file
+///
+/// Sequences and quality strings may span multiple lines. Empty lines
+/// between records are ignored, and trailing whitespace is removed from
+/// each line.
+///
+/// # Errors
+/// Returns an error if the reader fails, or if the content is not valid
+/// FASTQ.
+pub fn read_fastq(reader: impl BufRead, mask: SoftMask) -> Result<Vec<Record>, ParseError> {
+    fastq(Lines::new(reader, 0), mask)
+}
+
+fn fastq(mut lines: Lines<impl BufRead>, mask: SoftMask) -> Result<Vec<Record>, ParseError> {
+    let mut records = Vec::new();
+
+    while let Some(line) = lines.next()? {
+        if line.is_empty() {
+            continue;
+        }
+
+        let header = line
+            .strip_prefix(b"@")
+            .ok_or_else(|| ParseError::new(lines.line, ErrorKind::MissingHeader))?;
+        let mut record = new_record(header, lines.line)?;
+
+        // Sequence lines, until the separator
+        loop {
+            match lines.next()? {
+                Some(line) if line.starts_with(b"+") => break,
+                Some(line) => push_sequence(&mut record.seq, &line, mask, lines.line)?,
+                None => return Err(ParseError::new(lines.line, ErrorKind::MissingSeparator)),
+            }
+        }
+
+        // Quality lines, until it is as long as the sequence
+        let mut quality = Vec::with_capacity(record.seq.len());
+        while quality.len() < record.seq.len() {
+            match lines.next()? {
+                Some(line) => quality.extend_from_slice(&line),
+                None => break,
+            }
+        }
+
+        if quality.len() != record.seq.len() {
+            let kind = ErrorKind::QualityLength {
+                expected: record.seq.len(),
+                found: quality.len(),
+            };
+            return Err(ParseError::new(lines.line, kind));
+        }
+
+        record.quality = Some(quality);
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+/// Read all the records of a file, in the FASTQ format if the first
+/// non-empty line starts with `@` and in the FASTA format otherwise.
+///
+/// # Errors
+/// Returns an error if the reader fails, or if the content is not valid.
+pub fn read(mut reader: impl BufRead, mask: SoftMask) -> Result<Vec<Record>, ParseError> {
+    // Number of lines skipped while looking for the first non-empty one
+    let mut skipped = 0;
+    let is_fastq = loop {
+        let buf = reader
+            .fill_buf()
+            .map_err(|e| ParseError::new(skipped + 1, ErrorKind::Io(e)))?;
+        match buf.iter().find(|c| !c.is_ascii_whitespace()) {
+            Some(&c) => break c == b'@',
+            None if buf.is_empty() => break false,
+            None => {
+                skipped += buf.split(|&c| c == b'\n').count() - 1;
+                let len = buf.len();
+                reader.consume(len);
+            }
+        }
+    };
+
+    let lines = Lines::new(reader, skipped);
+    if is_fastq {
+        fastq(lines, mask)
+    } else {
+        fasta(lines, mask)
+    }
+}
+
+/// Collect the sequences of the records, to be used with
+/// [`dag::xmcsk`] or [`set::xmcsk`].
+///
+/// [`dag::xmcsk`]: `crate::dag::xmcsk`
+/// [`set::xmcsk`]: `crate::set::xmcsk`
+pub fn sequences(records: &[Record]) -> Vec<&[u8]> {
+    records.iter().map(|r| &r.seq[..]).collect()
+}
+
+fn new_record(header: &[u8], line: usize) -> Result<Record, ParseError> {
+    let header = String::from_utf8_lossy(header);
+    let header = header.trim();
+    let (name, description) = header
+        .find(char::is_whitespace)
+        .map_or((header, None), |i| {
+            (&header[..i], Some(header[i..].trim_start().to_string()))
+        });
+
+    if name.is_empty() {
+        return Err(ParseError::new(line, ErrorKind::EmptyName));
+    }
+
+    Ok(Record {
+        name: name.to_string(),
+        description,
+        seq: Vec::new(),
+        quality: None,
+    })
+}
+
+fn push_sequence(
+    seq: &mut Vec<u8>,
+    line: &[u8],
+    mask: SoftMask,
+    line_number: usize,
+) -> Result<(), ParseError> {
+    for &c in line {
+        if !(c.is_ascii_alphabetic() || c == b'*' || c == b'-') {
+            return Err(ParseError::new(line_number, ErrorKind::InvalidCharacter(c)));
+        }
+
+        match mask {
+            SoftMask::Keep => seq.push(c),
+            SoftMask::Unmask => seq.push(c.to_ascii_uppercase()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Iterate over the lines of a reader, without the line terminator and
+/// the trailing whitespace, keeping track of the current line number.
+struct Lines<R> {
+    reader: R,
+    line: usize,
+}
+
+impl<R: BufRead> Lines<R> {
+    /// Lines of `reader`, the first one being numbered `line + 1`
+    const fn new(reader: R, line: usize) -> Self {
+        Self { reader, line }
+    }
+
+    fn next(&mut self) -> Result<Option<Vec<u8>>, ParseError> {
+        let mut buf = Vec::new();
+        let read = self
+            .reader
+            .read_until(b'\n', &mut buf)
+            .map_err(|e| ParseError::new(self.line + 1, ErrorKind::Io(e)))?;
+
+        if read == 0 {
+            return Ok(None);
+        }
+
+        self.line += 1;
+        while buf.last().is_some_and(u8::is_ascii_whitespace) {
+            buf.pop();
+        }
+
+        Ok(Some(buf))
+    }
+}
+
+// === Tests ===
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fasta() {
+        let file = b"; comment\n>seq1 some description\nACGT \t\nac\r\n\n>seq2\n>seq3\nGGG\n";
+        let records = read_fasta(&file[..], SoftMask::Keep).unwrap();
+
+        assert_eq!(3, records.len());
+        assert_eq!("seq1", records[0].name);
+        assert_eq!(Some("some description"), records[0].description.as_deref());
+        assert_eq!(b"ACGTac", &records[0].seq[..]);
+        assert_eq!(None, records[0].quality);
+        assert_eq!("seq2", records[1].name);
+        assert!(records[1].seq.is_empty());
+        assert_eq!(b"GGG", &records[2].seq[..]);
+
+        let records = read_fasta(&file[..], SoftMask::Unmask).unwrap();
+        assert_eq!(b"ACGTAC", &records[0].seq[..]);
+    }
+
+    #[test]
+    fn test_fasta_errors() {
+        let err = read_fasta(&b"ACGT\n>seq\n"[..], SoftMask::Keep).unwrap_err();
+        assert_eq!(1, err.line());
+        assert!(matches!(err.kind(), ErrorKind::MissingHeader));
+
+        let err = read_fasta(&b">seq\nAC GT\n"[..], SoftMask::Keep).unwrap_err();
+        assert_eq!(2, err.line());
+        assert!(matches!(err.kind(), ErrorKind::InvalidCharacter(b' ')));
+
+        let err = read_fasta(&b">seq\nACGT\n> \nA\n"[..], SoftMask::Keep).unwrap_err();
+        assert_eq!(3, err.line());
+        assert!(matches!(err.kind(), ErrorKind::EmptyName));
+    }
+
+    #[test]
+    fn test_read() {
+        // Leading blank lines are counted
+        let file = b"\n  \n>seq\nACGT\nA!\n";
+        let err = read(&file[..], SoftMask::Keep).unwrap_err();
+        assert_eq!(5, err.line());
+        assert!(matches!(err.kind(), ErrorKind::InvalidCharacter(b'!')));
+
+        let file = b"\r\n\n@r1\nAC  \n+\nII\nA\n";
+        let err = read(&file[..], SoftMask::Keep).unwrap_err();
+        assert_eq!(7, err.line());
+        assert!(matches!(err.kind(), ErrorKind::MissingHeader));
+
+        let records = read(&b"\n\n>seq\nAC \n"[..], SoftMask::Keep).unwrap();
+        assert_eq!(vec![&b"AC"[..]], sequences(&records));
+    }
+
+    #[test]
+    fn test_fastq() {
+        let file = b"@r1 desc\nACgt\nAC\n+\nIIII\nII\n\n@r2\nTT\n+r2\n!!\n";
+        let records = read_fastq(&file[..], SoftMask::Unmask).unwrap();
+
+        assert_eq!(2, records.len());
+        assert_eq!("r1", records[0].name);
+        assert_eq!(b"ACGTAC", &records[0].seq[..]);
+        assert_eq!(Some(b"IIIIII".to_vec()), records[0].quality);
+        assert_eq!(b"TT", &records[1].seq[..]);
+        assert_eq!(Some(b"!!".to_vec()), records[1].quality);
+
+        let auto = read(&file[..], SoftMask::Unmask).unwrap();
+        assert_eq!(records, auto);
+    }
+
+    #[test]
+    fn test_fastq_errors() {
+        let err = read_fastq(&b"@r1\nACGT\n+\nIII\n"[..], SoftMask::Keep).unwrap_err();
+        assert_eq!(4, err.line());
+        assert!(matches!(
+            err.kind(),
+            ErrorKind::QualityLength {
+                expected: 4,
+                found: 3
+            }
+        ));
+
+        let err = read_fastq(&b"@r1\nACGT\n"[..], SoftMask::Keep).unwrap_err();
+        assert!(matches!(err.kind(), ErrorKind::MissingSeparator));
+
+        let err = read_fastq(&b"@r1\nA\n+\nI\nA\n"[..], SoftMask::Keep).unwrap_err();
+        assert_eq!(5, err.line());
+        assert!(matches!(err.kind(), ErrorKind::MissingHeader));
+    }
+}
@@ -0,0 +1,194 @@
+//! Length of a longest common subsequence of two sequences
+//!
+//! This module implements the bit-parallel algorithm of Allison and Dix,
+//! as improved by Hyyrö, computing the length of a longest common
+//! subsequence of two sequences without building the full dynamic
+//! programming table. A row of the table is encoded in a bit vector of
+//! as many bits as the shorter sequence, split into 64-bit words, and each
+//! element of the longer sequence updates it with a constant number of
+//! operations per word.
+//!
+//! The length is an upper bound on the length of the subsequences
+//! represented by an extended set of maximal common subsequences, and is
+//! the best value of `len` to compute a longest common subsequence with
+//! [`dag::xmcs2`](crate::dag::xmcs2).
+
+use std::cmp::min;
+
+/// Number of bits in a word of the bit vectors
+const WORD: usize = 64;
+
+/// Match masks of the distinct symbols of a sequence
+///
+/// A mask only stores its non-zero words: as every position of the
+/// sequence is set in a single mask, the masks take `O(n)` words for a
+/// sequence of length `n`, whatever the size of the alphabet. The
+/// symbols are only compared for equality, and are looked up with a
+/// linear scan.
+struct Masks<'a, T> {
+    /// Distinct symbols of the sequence, in order of first occurrence
+    symbols: Vec<&'a T>,
+    /// Number of words of a mask
+    words: usize,
+    /// Non-zero words of the mask of each symbol, with their index:
+    /// bit `i` of the mask of symbol `c` is set iff the element at
+    /// position `i` of the sequence is `c`
+    bits: Vec<Vec<(usize, u64)>>,
+}
+
+impl<'a, T> Masks<'a, T>
+where
+    T: Eq,
+{
+    /// Masks of the elements of `seq`
+    fn new(seq: &'a [T]) -> Self {
+        let mut res = Self {
+            symbols: Vec::new(),
+            words: seq.len().div_ceil(WORD),
+            bits: Vec::new(),
+        };
+
+        for (i, e) in seq.iter().enumerate() {
+            let id = res.id(e).unwrap_or_else(|| {
+                res.symbols.push(e);
+                res.bits.push(Vec::new());
+                res.bits.len() - 1
+            });
+
+            let mask = &mut res.bits[id];
+            let (w, bit) = (i / WORD, 1 << (i % WORD));
+            match mask.last_mut() {
+                Some((last, bits)) if *last == w => *bits |= bit,
+                _ => mask.push((w, bit)),
+            }
+        }
+
+        res
+    }
+
+    /// Identifier of `symbol`, `None` if it does not occur in the sequence
+    fn id(&self, symbol: &T) -> Option<usize> {
+        self.symbols.iter().position(|&s| s == symbol)
+    }
+}
+
+/// Compute the length of a longest common subsequence of `s1` and `s2`
+///
+/// Runs in time `O(n (⌈m / 64⌉ + σ) + m σ)` and uses `O(n + m)`
+/// memory, where `m` is the length of the shorter sequence, `n` the
+/// length of the longer one and `σ` the number of distinct elements of
+/// the shorter sequence.
+///
+/// # Examples
+/// ```
+/// use xmcs::lcs::lcs_length;
+///
+/// let s1 = b"ADBCBAD";
+/// let s2 = b"ADCBACD";
+/// let len = lcs_length(s1, s2);
+/// assert_eq!(6, len);
+///
+/// // No longer common subsequence can exist, use `len` to compute
+/// // a longest common subsequence
+/// let dag = xmcs::dag::xmcs2(len, s1, s2);
+/// assert_eq!(Some(len), dag.extract_lcs().map(|s| s.len()));
+/// ```
+pub fn lcs_length<T: Eq>(s1: &[T], s2: &[T]) -> usize {
+    // The bit vectors have the length of the shorter sequence
+    let (s1, s2) = if s1.len() <= s2.len() {
+        (s1, s2)
+    } else {
+        (s2, s1)
+    };
+    let masks = Masks::new(s1);
+
+    // Bit `i` is unset iff the length of a longest common subsequence of
+    // `s1[..=i]` and the current prefix of `s2` increases at position `i`
+    let mut v = vec![!0_u64; masks.words];
+
+    for id in s2.iter().filter_map(|e| masks.id(e)) {
+        let mut mask = masks.bits[id].iter().peekable();
+        let mut carry = false;
+        for (w, v) in v.iter_mut().enumerate() {
+            let m = mask.next_if(|&&(i, _)| i == w).map_or(0, |&(_, m)| m);
+            let u = *v & m;
+            let (sum, c1) = v.overflowing_add(u);
+            let (sum, c2) = sum.overflowing_add(u64::from(carry));
+            carry = c1 || c2;
+            *v = sum | (*v & !u);
+        }
+    }
+
+    // The padding bits of the last word may have been modified by the
+    // carries, only the first `|s1|` bits are counted
+    let ones = v
+        .iter()
+        .enumerate()
+        .map(|(w, &word)| {
+            let bits = min(WORD, s1.len() - w * WORD);
+            let word = if bits == WORD {
+                word
+            } else {
+                word & ((1 << bits) - 1)
+            };
+            word.count_ones() as usize
+        })
+        .sum::<usize>();
+
+    s1.len() - ones
+}
+
+// === Tests ===
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::reference::{self, random_sequences};
+
+    #[test]
+    fn test_lcs_length() {
+        assert_eq!(0, lcs_length::<u8>(b"", b""));
+        assert_eq!(0, lcs_length(b"ABC", b""));
+        assert_eq!(0, lcs_length(b"", b"ABC"));
+        assert_eq!(0, lcs_length(b"ABC", b"DEF"));
+        assert_eq!(3, lcs_length(b"ABC", b"ABC"));
+        assert_eq!(4, lcs_length(b"ABCBDAB", b"BDCABA"));
+    }
+
+    #[test]
+    fn test_large_alphabet() {
+        // Every element is distinct, and half of them only occur in one
+        // of the sequences
+        let s1 = (0..1000_u32).collect::<Vec<_>>();
+        let s2 = (500..1500_u32).rev().step_by(3).collect::<Vec<_>>();
+
+        assert_eq!(1, lcs_length(&s1, &s2));
+        assert_eq!(1, lcs_length(&s2, &s1));
+
+        let s2 = (0..2000_u32).filter(|e| e % 7 != 0).collect::<Vec<_>>();
+        assert_eq!(reference::lcs_length(&[&s1, &s2]), lcs_length(&s1, &s2));
+        assert_eq!(reference::lcs_length(&[&s2, &s1]), lcs_length(&s2, &s1));
+    }
+
+    #[test]
+    fn test_eq_only() {
+        // Elements that can only be compared
+        #[derive(PartialEq, Eq)]
+        struct E(u8);
+
+        let s1 = b"ABCBDAB".iter().map(|&c| E(c)).collect::<Vec<_>>();
+        let s2 = b"BDCABA".iter().map(|&c| E(c)).collect::<Vec<_>>();
+        assert_eq!(4, lcs_length(&s1, &s2));
+    }
+
+    #[test]
+    fn test_random() {
+        for seqs in random_sequences(200, 0x2545_f491_4f6c_dd1d, 2..=2, 0..=299, 6) {
+            let (s1, s2) = (&seqs[0], &seqs[1]);
+
+            assert_eq!(reference::lcs_length(&[s1, s2]), lcs_length(s1, s2));
+            assert_eq!(reference::lcs_length(&[s2, s1]), lcs_length(s2, s1));
+        }
+    }
+}
@@ -1,3 +1,12 @@
+//! Extended sets of maximal common subsequences
+//!
+//! This crate implements an algorithm to find a longest common
+//! subsequence of a set of sequences, using an extended set of
+//! maximal common subsequences (xMCS).
+//!
+//! Two backends are available: [`set`] stores the subsequences in hash
+//! sets, [`dag`] represents them as a directed acyclic graph.
+
 #![deny(unsafe_code)]
 #![deny(unreachable_patterns)]
 #![warn(missing_copy_implementations)]
@@ -11,6 +20,15 @@
 #![allow(clippy::missing_const_for_fn)]
 #![allow(clippy::inline_always)]
 
+pub mod alphabet;
+pub mod automaton;
+pub mod bounds;
 pub mod dag;
+pub mod error;
+pub mod io;
+pub mod lcs;
+pub mod reference;
 pub mod set;
 pub mod substr;
+
+pub use error::Error;
@@ -0,0 +1,290 @@
+//! Classical dynamic programming computation of a longest common
+//! subsequence
+//!
+//! This module fills the full table of the lengths of the longest common
+//! subsequences of all the tails of the sequences. It uses memory and
+//! time proportional to the product of the lengths of the sequences, so
+//! it is only usable for short sequences or a small number of them, but
+//! it is simple enough to be trusted and is used to validate the other
+//! algorithms of this crate.
+
+#[cfg(test)]
+use std::ops::RangeInclusive;
+
+/// Table of the lengths of the longest common subsequences of all the
+/// tails of the sequences
+struct Table<'s, T> {
+    /// Sequences
+    seqs: &'s [&'s [T]],
+    /// Offset in `lengths` of a step in each sequence
+    strides: Vec<usize>,
+    /// Length of a longest common subsequence of the tails starting at
+    /// each tuple of positions, stored in mixed radix order
+    lengths: Vec<usize>,
+}
+
+impl<'s, T> Table<'s, T>
+where
+    T: Eq,
+{
+    fn new(seqs: &'s [&'s [T]]) -> Self {
+        let mut strides = Vec::with_capacity(seqs.len());
+        let mut size = 1_usize;
+        for s in seqs.iter().rev() {
+            strides.push(size);
+            size = size
+                .checked_mul(s.len() + 1)
+                .expect("dynamic programming table too large");
+        }
+        strides.reverse();
+
+        let mut res = Self {
+            seqs,
+            strides,
+            lengths: vec![0; size],
+        };
+
+        let mut pos = seqs.iter().map(|s| s.len()).collect::<Vec<_>>();
+        for index in (0..size).rev() {
+            res.lengths[index] = res.compute(&pos, index);
+            res.decrement(&mut pos);
+        }
+
+        res
+    }
+
+    /// Compute the length at `pos`, whose index is `index`, from the
+    /// lengths at the following positions
+    fn compute(&self, pos: &[usize], index: usize) -> usize {
+        if self.is_end(pos) {
+            0
+        } else if self.is_match(pos) {
+            self.lengths[index + self.strides.iter().sum::<usize>()] + 1
+        } else {
+            self.strides
+                .iter()
+                .map(|&stride| self.lengths[index + stride])
+                .max()
+                .unwrap_or(0)
+        }
+    }
+
+    /// Returns whether one of the tails starting at `pos` is empty
+    fn is_end(&self, pos: &[usize]) -> bool {
+        pos.iter().zip(self.seqs).any(|(&p, s)| p == s.len())
+    }
+
+    /// Returns whether all the tails starting at `pos` start with the
+    /// same element
+    fn is_match(&self, pos: &[usize]) -> bool {
+        let first = &self.seqs[0][pos[0]];
+        pos.iter().zip(self.seqs).all(|(&p, s)| &s[p] == first)
+    }
+
+    /// Move to the previous tuple of positions in mixed radix order
+    fn decrement(&self, pos: &mut [usize]) {
+        for (p, s) in pos.iter_mut().zip(self.seqs).rev() {
+            if *p == 0 {
+                *p = s.len();
+            } else {
+                *p -= 1;
+                return;
+            }
+        }
+    }
+}
+
+impl<T> Table<'_, T>
+where
+    T: Eq + Copy,
+{
+    /// Follow the table from the beginning of the sequences to build a
+    /// longest common subsequence
+    fn traceback(&self) -> Vec<T> {
+        let mut res = Vec::with_capacity(self.lengths[0]);
+        let mut pos = vec![0; self.seqs.len()];
+        let mut index = 0;
+
+        while !self.is_end(&pos) {
+            if self.is_match(&pos) {
+                res.push(self.seqs[0][pos[0]]);
+                for p in &mut pos {
+                    *p += 1;
+                }
+                index += self.strides.iter().sum::<usize>();
+            } else {
+                // A step that keeps the length exists, as it is the
+                // maximum over all the steps
+                let i = (0..pos.len())
+                    .find(|&i| self.lengths[index + self.strides[i]] == self.lengths[index])
+                    .unwrap_or(0);
+                pos[i] += 1;
+                index += self.strides[i];
+            }
+        }
+
+        res
+    }
+}
+
+/// Compute the length of a longest common subsequence of `seqs`
+///
+/// Runs in time `O(k ∏ (|s| + 1))` and uses `O(∏ (|s| + 1))` memory.
+///
+/// # Panics
+/// Panics if the size of the table overflows `usize`.
+///
+/// # Examples
+/// ```
+/// # use xmcs::reference::lcs_length;
+/// assert_eq!(4, lcs_length(&[&b"ABCBDAB"[..], b"BDCABA"]));
+/// ```
+pub fn lcs_length<T: Eq>(seqs: &[&[T]]) -> usize {
+    if seqs.is_empty() {
+        return 0;
+    }
+    Table::new(seqs).lengths[0]
+}
+
+/// Compute a longest common subsequence of `seqs`
+///
+/// Runs in time `O(k ∏ (|s| + 1))` and uses `O(∏ (|s| + 1))` memory.
+///
+/// # Panics
+/// Panics if the size of the table overflows `usize`.
+///
+/// # Examples
+/// ```
+/// # use xmcs::reference::lcs;
+/// let lcs = lcs(&[&b"ABCBDAB"[..], b"BDCABA", b"BCBA"]);
+/// assert_eq!(b"BCBA", &lcs[..]);
+/// ```
+pub fn lcs<T: Eq + Copy>(seqs: &[&[T]]) -> Vec<T> {
+    if seqs.is_empty() {
+        return Vec::new();
+    }
+    Table::new(seqs).traceback()
+}
+
+/// Random sets of sequences used by the tests, with a xorshift generator
+///
+/// Each of the `count` sets has a number of sequences in `k`, of lengths
+/// in `len`, over the first letters of the alphabet: their number is
+/// drawn for each set between 1 and `sigma`.
+#[cfg(test)]
+pub(crate) fn random_sequences(
+    count: usize,
+    seed: u64,
+    k: RangeInclusive<usize>,
+    len: RangeInclusive<usize>,
+    sigma: usize,
+) -> Vec<Vec<Vec<u8>>> {
+    use std::convert::TryFrom;
+
+    let mut state = seed;
+    let mut random = |range: &RangeInclusive<usize>| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let n = u64::try_from(range.end() - range.start() + 1).unwrap();
+        range.start() + usize::try_from(state % n).unwrap()
+    };
+
+    (0..count)
+        .map(|_| {
+            let k = random(&k);
+            let sigma = random(&(1..=sigma));
+            (0..k)
+                .map(|_| {
+                    (0..random(&len))
+                        .map(|_| b'A' + u8::try_from(random(&(0..=sigma - 1))).unwrap())
+                        .collect()
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// === Tests ===
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cmp::Ordering;
+
+    use crate::automaton::Automaton;
+
+    fn is_common_subsequence(sub: &[u8], seqs: &[&[u8]]) -> bool {
+        seqs.iter().all(|s| Automaton::new(s).is_subsequence(sub))
+    }
+
+    #[test]
+    fn test_lcs() {
+        assert_eq!(0, lcs_length::<u8>(&[]));
+        assert_eq!(3, lcs_length(&[b"ABC"]));
+        assert_eq!(0, lcs_length(&[&b"ABC"[..], b""]));
+        assert_eq!(2, lcs_length(&[&b"ABC"[..], b"ACB", b"BAC"]));
+
+        for seqs in random_sequences(300, 0x2545_f491_4f6c_dd1d, 1..=4, 0..=10, 4) {
+            let seqs = seqs.iter().map(Vec::as_slice).collect::<Vec<_>>();
+            let res = lcs(&seqs);
+
+            assert_eq!(lcs_length(&seqs), res.len());
+            assert!(is_common_subsequence(&res, &seqs));
+            if let [s1, s2] = seqs[..] {
+                assert_eq!(crate::lcs::lcs_length(s1, s2), res.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_dag_xmcsk() {
+        for (i, seqs) in random_sequences(500, 0x9e37_79b9_7f4a_7c15, 1..=4, 0..=10, 4)
+            .iter()
+            .enumerate()
+        {
+            let seqs = seqs.iter().map(Vec::as_slice).collect::<Vec<_>>();
+            let expected = lcs_length(&seqs);
+
+            for len in 0..=expected + 1 {
+                let res = crate::dag::xmcsk(len, &seqs).extract_lcs();
+                if let Some(res) = &res {
+                    assert!(is_common_subsequence(res, &seqs));
+                }
+
+                let res = res.map_or(0, |s| s.len());
+                if len <= expected {
+                    assert_eq!(expected, res, "{i} {len} {seqs:?}");
+                } else {
+                    assert_eq!(Ordering::Less, res.cmp(&len), "{i} {len} {seqs:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_set_xmcsk() {
+        for (i, seqs) in random_sequences(500, 0xd1b5_4a32_d192_ed03, 1..=4, 0..=10, 4)
+            .iter()
+            .enumerate()
+        {
+            let seqs = seqs.iter().map(Vec::as_slice).collect::<Vec<_>>();
+            let expected = lcs_length(&seqs);
+
+            for len in 0..=expected + 1 {
+                let set = crate::set::xmcsk(len, &seqs);
+                for s in &set {
+                    assert!(is_common_subsequence(s, &seqs));
+                }
+
+                let res = set.iter().map(Vec::len).max().unwrap_or(0);
+                if len <= expected {
+                    assert_eq!(expected, res, "{i} {len} {seqs:?}");
+                } else {
+                    assert_eq!(Ordering::Less, res.cmp(&len), "{i} {len} {seqs:?}");
+                }
+            }
+        }
+    }
+}
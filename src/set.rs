@@ -4,11 +4,20 @@
 //! common subsequences of 2 or several sequences.
 //! The implementation found in this module uses `HashSet`s to contains
 //! the full set of subsequences.
+//!
+//! While computing the set of two sequences, the subsequences are
+//! stored as persistent lists sharing their suffixes, and the set
+//! computed for each pair of tails is memoised.
 
-use std::collections::HashSet;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
+use std::rc::Rc;
 
+use crate::bounds::check_upper_bound;
+use crate::error::check_length;
 use crate::substr::SubString;
+use crate::Error;
 
 /// Compute an extended set of maximal common subsequences of all
 /// the sequences in `seqs`, of sizes at least `len`.
@@ -24,13 +33,27 @@ pub fn xmcsk<T: Eq + Hash + Copy>(len: usize, seqs: &[&[T]]) -> HashSet<Vec<T>>
         let xmcs = xmcsk(len, &seqs[..(k - 1)]);
         for s in xmcs {
             let substrings = xmcs2(len, &s, seqs[k - 1]);
-            res.extend(substrings.into_iter());
+            res.extend(substrings);
         }
     }
 
     res
 }
 
+/// Same as [`xmcsk`], but returns an error instead of panicking if the
+/// parameters are invalid.
+///
+/// # Errors
+/// Returns [`Error::LengthTooLarge`] if `len` is larger than the length
+/// of one of the sequences, or than the bound on the length of a longest
+/// common subsequence of
+/// [`check_upper_bound`].
+pub fn try_xmcsk<T: Eq + Hash + Copy>(len: usize, seqs: &[&[T]]) -> Result<HashSet<Vec<T>>, Error> {
+    check_length(len, seqs)?;
+    check_upper_bound(len, seqs)?;
+    Ok(xmcsk(len, seqs))
+}
+
 /// Compute an extended set of maximal common subsequences of s1 and s2,
 /// of sizes at least `len`.
 ///
@@ -38,57 +61,222 @@ pub fn xmcsk<T: Eq + Hash + Copy>(len: usize, seqs: &[&[T]]) -> HashSet<Vec<T>>
 /// `m = min(|s1|, |s2|)`, `Δ = n - len` and `δ = m - len`
 pub fn xmcs2<T: Eq + Hash + Copy>(len: usize, s1: &[T], s2: &[T]) -> HashSet<Vec<T>> {
     let n = std::cmp::max(s1.len(), s2.len());
+    let m = std::cmp::min(s1.len(), s2.len());
+    // No common subsequence long enough, and the band of the table
+    // would not contain the end of both sequences
+    if len > m {
+        return HashSet::new();
+    }
     let delta = n - len;
     let substring = SubString::new(s1, s2, delta);
 
     xmcs2_impl(len, s1, s2, &substring)
 }
 
+/// Same as [`xmcs2`], but returns an error instead of panicking if the
+/// parameters are invalid.
+///
+/// # Errors
+/// Returns [`Error::LengthTooLarge`] if `len` is larger than the length
+/// of one of the sequences, or than the bound on the length of a longest
+/// common subsequence of
+/// [`check_upper_bound`].
+pub fn try_xmcs2<T: Eq + Hash + Copy>(
+    len: usize,
+    s1: &[T],
+    s2: &[T],
+) -> Result<HashSet<Vec<T>>, Error> {
+    check_length(len, &[s1, s2])?;
+    check_upper_bound(len, &[s1, s2])?;
+    Ok(xmcs2(len, s1, s2))
+}
+
+/// Identifier of the empty list in [`Lists`]
+const EMPTY: usize = 0;
+
+/// Persistent lists, sharing their common suffixes
+///
+/// The lists are hash-consed: a list is only built once, so two lists
+/// are equal iff they have the same identifier, and prepending an
+/// element takes constant time.
+struct Lists<T> {
+    /// First element and identifier of the tail of each non-empty
+    /// list, the list with identifier `id` being at index `id - 1`
+    cells: Vec<(T, usize)>,
+    /// Identifier of each non-empty list
+    ids: HashMap<(T, usize), usize>,
+}
+
+impl<T: Eq + Hash + Copy> Lists<T> {
+    fn new() -> Self {
+        Self {
+            cells: Vec::new(),
+            ids: HashMap::new(),
+        }
+    }
+
+    /// Identifier of the list starting with `head` followed by `tail`
+    fn cons(&mut self, head: T, tail: usize) -> usize {
+        let cells = &mut self.cells;
+        *self.ids.entry((head, tail)).or_insert_with(|| {
+            cells.push((head, tail));
+            cells.len()
+        })
+    }
+
+    /// Identifiers of all the suffixes of `seq`, indexed by their length
+    fn suffixes(&mut self, seq: &[T]) -> Vec<usize> {
+        let mut res = Vec::with_capacity(seq.len() + 1);
+        res.push(EMPTY);
+        for &e in seq.iter().rev() {
+            let tail = res[res.len() - 1];
+            res.push(self.cons(e, tail));
+        }
+        res
+    }
+
+    /// Elements of the list `id`
+    fn to_vec(&self, mut id: usize) -> Vec<T> {
+        let mut res = Vec::new();
+        while id != EMPTY {
+            let (head, tail) = self.cells[id - 1];
+            res.push(head);
+            id = tail;
+        }
+        res
+    }
+}
+
+/// Set of lists, as their sorted identifiers
+type Set = Rc<[usize]>;
+
+/// Progress of a call of [`xmcs2_impl`] in its explicit call stack,
+/// giving the meaning of the set returned by the last finished call
+enum Step {
+    /// Not started yet
+    Enter,
+    /// Subsequences after two matching elements
+    Element,
+    /// Subsequences without the first element of the first sequence
+    Skip1,
+    /// Subsequences without the first element of the second sequence,
+    /// along with the result of `Skip1`
+    Skip2(Set),
+}
+
+/// Memoises the set of subsequences computed for each position, the
+/// subsequences being stored as persistent lists. Uses an explicit
+/// stack of calls so that long sequences do not overflow the thread
+/// stack.
 fn xmcs2_impl<T: Eq + Hash + Copy>(
     len: usize,
     s1: &[T],
     s2: &[T],
     substr: &SubString,
 ) -> HashSet<Vec<T>> {
-    let l1 = s1.len();
-    let l2 = s2.len();
-    // Too much elements removed, no subsequence long enough here
-    if len > l1 || len > l2 || l1 == 0 || l2 == 0 {
-        return HashSet::new();
+    let mut lists = Lists::new();
+    let suffixes1 = lists.suffixes(s1);
+    let suffixes2 = lists.suffixes(s2);
+    let empty: Set = Rc::new([]);
+    let mut memo = HashMap::<(usize, usize, usize), Set>::new();
+
+    let mut stack = vec![(len, s1, s2, Step::Enter)];
+    // Set returned by the last finished call
+    let mut ret = Rc::clone(&empty);
+
+    while let Some((len, s1, s2, step)) = stack.pop() {
+        let l1 = s1.len();
+        let l2 = s2.len();
+
+        ret = match step {
+            Step::Enter => {
+                // Too much elements removed, no subsequence long enough here
+                if len > l1 || len > l2 {
+                    ret = Rc::clone(&empty);
+                    continue;
+                }
+
+                if let Some(set) = memo.get(&(len, l1, l2)) {
+                    ret = Rc::clone(set);
+                    continue;
+                }
+
+                // One is a subsequence of another, return it. This
+                // includes the case where one of them is empty, so that
+                // the common subsequences ending there are kept.
+                if substr.is_substring_from_end(l1, l2) {
+                    let id = if l1 < l2 {
+                        suffixes1[l1]
+                    } else {
+                        suffixes2[l2]
+                    };
+                    Rc::new([id])
+                } else if s1[0] == s2[0] {
+                    // saturating_sub: do not undeflow at 0. The len is not
+                    // important anymore when it reaches 0 so this is not an issue
+                    stack.push((len, s1, s2, Step::Element));
+                    stack.push((len.saturating_sub(1), &s1[1..], &s2[1..], Step::Enter));
+                    continue;
+                } else {
+                    stack.push((len, s1, s2, Step::Skip1));
+                    stack.push((len, &s1[1..], s2, Step::Enter));
+                    continue;
+                }
+            }
+            Step::Element => {
+                let mut set = ret
+                    .iter()
+                    .map(|&id| lists.cons(s1[0], id))
+                    .collect::<Vec<_>>();
+                set.sort_unstable();
+                set.into()
+            }
+            Step::Skip1 => {
+                let res1 = std::mem::replace(&mut ret, Rc::clone(&empty));
+                stack.push((len, s1, s2, Step::Skip2(res1)));
+                stack.push((len, s1, &s2[1..], Step::Enter));
+                continue;
+            }
+            Step::Skip2(res1) => union(&res1, &ret),
+        };
+
+        memo.insert((len, l1, l2), Rc::clone(&ret));
     }
 
-    // One is a subsequence of another, return it
-    if substr.is_substring_from_end(l1, l2) {
-        let mut res = HashSet::new();
-        if l1 < l2 {
-            res.insert(s1.to_vec());
-        } else {
-            res.insert(s2.to_vec());
-        }
-        return res;
+    ret.iter().map(|&id| lists.to_vec(id)).collect()
+}
+
+/// Union of two sets of sorted identifiers
+fn union(set1: &Set, set2: &Set) -> Set {
+    if set1.is_empty() {
+        return Rc::clone(set2);
+    }
+    if set2.is_empty() {
+        return Rc::clone(set1);
     }
 
-    let u1 = s1[0];
-    let u2 = s2[0];
-
-    if u1 == u2 {
-        // saturating_sub: do not undeflow at 0. The len is not
-        // important anymore when it reaches 0 so this is not an issue
-        let len = len.saturating_sub(1);
-        let res = xmcs2_impl(len, &s1[1..], &s2[1..], substr);
-        res.into_iter()
-            .map(|mut s| {
-                s.insert(0, u1);
-                s
-            }) // Very inefficient
-            .collect::<HashSet<Vec<T>>>()
-    } else {
-        let res1 = xmcs2_impl(len, &s1[1..], s2, substr);
-        let res2 = xmcs2_impl(len, s1, &s2[1..], substr);
-        res1.into_iter()
-            .chain(res2.into_iter())
-            .collect::<HashSet<Vec<T>>>()
+    let mut res = Vec::with_capacity(set1.len() + set2.len());
+    let (mut i, mut j) = (0, 0);
+    while i < set1.len() && j < set2.len() {
+        match set1[i].cmp(&set2[j]) {
+            Ordering::Less => {
+                res.push(set1[i]);
+                i += 1;
+            }
+            Ordering::Greater => {
+                res.push(set2[j]);
+                j += 1;
+            }
+            Ordering::Equal => {
+                res.push(set1[i]);
+                i += 1;
+                j += 1;
+            }
+        }
     }
+    res.extend_from_slice(&set1[i..]);
+    res.extend_from_slice(&set2[j..]);
+    res.into()
 }
 
 #[cfg(test)]
@@ -126,4 +314,45 @@ mod test {
 
         assert!(expected.is_subset(&res));
     }
+
+    #[test]
+    fn test_sequence_end() {
+        // The common subsequences may end where one of the sequences ends
+        let res = xmcs2(2, b"ABC", b"ABD");
+        assert_eq!(HashSet::from([b"AB".to_vec()]), res);
+
+        // Longer than one of the sequences
+        assert!(xmcs2(3, b"AB", b"ABC").is_empty());
+    }
+
+    #[test]
+    fn test_long_sequences() {
+        // Far more elements than calls fitting in the stack of a test
+        // thread if each element needed a recursive call
+        let n = 200_000;
+        let s1 = (0..n).map(|i| b"ACGT"[i * 7 % 11 % 4]).collect::<Vec<_>>();
+        let mut s2 = s1.clone();
+        s2.remove(n / 2);
+        s2.remove(n / 3);
+
+        let res = xmcs2(n - 3, &s1, &s2);
+        assert!(res.contains(&s2));
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = try_xmcs2(5, b"ABCDEF", b"ABCD");
+        assert_eq!(Err(Error::LengthTooLarge { len: 5, max: 4 }), err);
+
+        let err = try_xmcsk(4, &[b"ABCDEF", b"ABCD", b"ABC"]);
+        assert_eq!(Err(Error::LengthTooLarge { len: 4, max: 3 }), err);
+
+        let err = try_xmcsk(3, &[b"ABCD", b"DCBA", b"ABDC"]);
+        assert_eq!(Err(Error::LengthTooLarge { len: 3, max: 1 }), err);
+
+        assert_eq!(
+            Ok(xmcs2(2, b"ABCD", b"ACBD")),
+            try_xmcs2(2, b"ABCD", b"ACBD")
+        );
+    }
 }
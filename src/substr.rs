@@ -3,15 +3,17 @@
 //! This module contains a structure used to precompute and the answer in
 //! constant time whether the tail of a sequence is a subsequence of another
 
+use crate::Error;
+
 #[doc(hidden)]
 const fn distance(a: usize, b: usize) -> usize {
-    if a > b {
-        a - b
-    } else {
-        b - a
-    }
+    a.abs_diff(b)
 }
 
+/// Pairs of positions `(p1, p2)` of equal elements in two sequences,
+/// returned by [`SubString::embedding_at`]
+pub type Embedding = Vec<(usize, usize)>;
+
 /// Struct used to precompute whether a sequence is a subsequence of
 /// another
 ///
@@ -24,7 +26,8 @@ const fn distance(a: usize, b: usize) -> usize {
 /// `s2[j..]` or `s2[j..]` a subsequence of `s1[i..]`.
 ///
 /// It only needs to do a precomputation in time `O(|s1| * delta)`
-/// beforehand.
+/// beforehand. The results are stored in a packed bitset, using
+/// `|s1| * (2 * delta + 1)` bits.
 ///
 /// # Examples
 /// ```
@@ -45,7 +48,7 @@ pub struct SubString {
     d1: usize,
     d2: usize,
     delta: usize,
-    table: Vec<bool>, // TODO: use something more optimized for this?
+    table: BitSet,
 }
 
 impl SubString {
@@ -61,6 +64,21 @@ impl SubString {
         Self::compute(s1, s2, delta)
     }
 
+    /// Same as [`new`], but returns an error instead of panicking.
+    ///
+    /// # Errors
+    /// Returns [`Error::LengthDifference`] if `||s1| - |s2|| > delta`.
+    ///
+    /// [`new`]: `SubString::new`
+    pub fn try_new<T: Eq>(s1: &[T], s2: &[T], delta: usize) -> Result<Self, Error> {
+        let (len1, len2) = (s1.len(), s2.len());
+        if distance(len1, len2) > delta {
+            return Err(Error::LengthDifference { len1, len2, delta });
+        }
+
+        Ok(Self::compute(s1, s2, delta))
+    }
+
     /// Returns whether the tail of one of the sequence is a subsequence of
     /// the tail of the other sequence
     ///
@@ -102,7 +120,28 @@ impl SubString {
             return true;
         }
 
-        self.table[self.index(i, j)]
+        self.table.get(self.index(i, j))
+    }
+
+    /// Same as [`is_substring_at`], but returns an error instead of
+    /// panicking.
+    ///
+    /// # Errors
+    /// Returns [`Error::IndexOutOfBounds`] if `i` or `j` is larger than
+    /// the length of its sequence, and [`Error::IndexOutOfBand`] if the
+    /// distance between `i` and `j` is larger than `delta`.
+    ///
+    /// [`is_substring_at`]: `SubString::is_substring_at`
+    pub fn try_is_substring_at(&self, i: usize, j: usize) -> Result<bool, Error> {
+        if i > self.d1 || j > self.d2 {
+            return Err(Error::IndexOutOfBounds { i, j });
+        }
+        if distance(i, j) > self.delta {
+            let delta = self.delta;
+            return Err(Error::IndexOutOfBand { i, j, delta });
+        }
+
+        Ok(self.is_substring_at(i, j))
     }
 
     /// Returns whether the tail of one of the sequence is a subsequence
@@ -118,6 +157,83 @@ impl SubString {
         self.is_substring_at(i, j)
     }
 
+    /// Returns how the tail of one of the sequence is embedded in the
+    /// tail of the other sequence
+    ///
+    /// If the shorter of `s1[i..]` and `s2[j..]` is a subsequence of the
+    /// other one (see [`is_substring_at`]), returns the positions of its
+    /// leftmost embedding: the list of pairs of indices `(p1, p2)` such
+    /// that `s1[p1] == s2[p2]`, one for each element of the shorter
+    /// tail. Returns `None` otherwise.
+    ///
+    /// The embedding is read from the precomputed results: an element is
+    /// matched as soon as the rest of the shorter tail can still be
+    /// embedded after it. `s1` and `s2` must be the sequences used at
+    /// construction, they are only used to compare the elements.
+    ///
+    /// Runs in time `O(max(|s1| - i, |s2| - j))`.
+    ///
+    /// # Panics
+    /// Panics if the lengths of `s1` and `s2` are not the lengths of the
+    /// sequences used at construction, or in the same cases as
+    /// [`is_substring_at`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # use xmcs::substr::SubString;
+    /// let (s1, s2) = (b"CABC", b"DBABDCD");
+    /// let res = SubString::new(s1, s2, 3);
+    ///
+    /// // "ABC" is found at positions 2, 3 and 5 of "DBABDCD"
+    /// let embedding = res.embedding_at(s1, s2, 1, 2);
+    /// assert_eq!(Some(vec![(1, 2), (2, 3), (3, 5)]), embedding);
+    ///
+    /// // "BC" is not a substring of "DCD"
+    /// assert_eq!(None, res.embedding_at(s1, s2, 2, 4));
+    /// ```
+    ///
+    /// [`is_substring_at`]: `SubString::is_substring_at`
+    pub fn embedding_at<T: Eq>(
+        &self,
+        s1: &[T],
+        s2: &[T],
+        mut i: usize,
+        mut j: usize,
+    ) -> Option<Embedding> {
+        assert_eq!(self.d1, s1.len());
+        assert_eq!(self.d2, s2.len());
+
+        if !self.is_substring_at(i, j) {
+            return None;
+        }
+
+        let mut res = Vec::with_capacity((self.d1 - i).min(self.d2 - j));
+        // The shorter tail is embedded in the other one, following the
+        // recurrence used by `compute`
+        while i < self.d1 && j < self.d2 {
+            if s1[i] == s2[j] && self.is_substring_at(i + 1, j + 1) {
+                res.push((i, j));
+                i += 1;
+                j += 1;
+            } else if self.d1 - i < self.d2 - j {
+                // Then `s1[i..]` is a subsequence of `s2[j + 1..]`
+                j += 1;
+            } else {
+                // Then `s2[j..]` is a subsequence of `s1[i + 1..]`
+                i += 1;
+            }
+        }
+
+        Some(res)
+    }
+
+    /// Returns the number of bytes allocated to store the precomputed
+    /// results
+    pub fn memory_usage(&self) -> usize {
+        self.table.memory_usage()
+    }
+
     #[doc(hidden)]
     fn compute<T: Eq>(s1: &[T], s2: &[T], delta: usize) -> Self {
         use std::cmp::Ordering;
@@ -127,8 +243,7 @@ impl SubString {
 
         assert!(distance(d1, d2) <= delta);
 
-        let mut res = Vec::new();
-        res.resize(d1 * (2 * delta + 1), false);
+        let mut res = BitSet::new(d1 * (2 * delta + 1));
 
         let index = |i: usize, j: usize| Self::index_with(i, j, delta);
 
@@ -155,29 +270,29 @@ impl SubString {
                     // s1[d1-k..] == s2[d2-k..] iff
                     // s1[d1-k+1..] == s2[d2-k+1..] and s1[d1-k] == s2[d2-k]
                     Ordering::Equal =>
-                        res[index(i + 1, j + 1)] && s1[i] == s2[j],
+                        res.get(index(i + 1, j + 1)) && s1[i] == s2[j],
 
                     // s1[d1-1..] is a substring of s2[d2-k..] iff
                     // s1[d1-1..] is a substring of s2[d2-k+1..]
                     // or s1[d1-1] == s2[d2-k]
                     Ordering::Less if end_i == 0 =>
-                        res[index(i, j + 1)] || s1[i] == s2[j],
+                        res.get(index(i, j + 1)) || s1[i] == s2[j],
 
                     // s1[d1-i..] is a substring of s2[d2-j..] iff
                     // s1[d1-i..] is a substring of s2[d2-j+1..] or
                     // s1[d1-i+1..] is a substring of s2[d2-j+1..]
                     //   and s1[d1-i] == s2[d2-j]
                     Ordering::Less => 
-                        res[index(i, j + 1)] || (res[index(i + 1, j + 1)] && s1[i] == s2[j]),
+                        res.get(index(i, j + 1)) || (res.get(index(i + 1, j + 1)) && s1[i] == s2[j]),
 
                     // etc...
                     Ordering::Greater if end_j == 0 =>
-                        res[index(i + 1, j)] || s1[i] == s2[j],
+                        res.get(index(i + 1, j)) || s1[i] == s2[j],
                     Ordering::Greater =>
-                        res[index(i + 1, j)] || (res[index(i + 1, j + 1)] && s1[i] == s2[j]),
+                        res.get(index(i + 1, j)) || (res.get(index(i + 1, j + 1)) && s1[i] == s2[j]),
                 };
 
-                res[index(i, j)] = is_substr;
+                res.set(index(i, j), is_substr);
             }
         }
 
@@ -200,6 +315,39 @@ impl SubString {
     }
 }
 
+/// Array of bits packed in 64-bit words
+struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    /// Create a bitset of `len` bits set to `false`
+    fn new(len: usize) -> Self {
+        Self {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    #[inline(always)]
+    fn get(&self, index: usize) -> bool {
+        self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    #[inline(always)]
+    fn set(&mut self, index: usize, value: bool) {
+        let mask = 1 << (index % 64);
+        if value {
+            self.words[index / 64] |= mask;
+        } else {
+            self.words[index / 64] &= !mask;
+        }
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.words.len() * size_of::<u64>()
+    }
+}
+
 // === Tests ===
 
 #[cfg(test)]
@@ -222,6 +370,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_substring_1() {
         let res = SubString::new(b"ACBBABCBCACBACBCBBABBCAC", b"ABCBABAAABACBABCABCA", 8);
 
@@ -252,6 +401,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_substring_2() {
         let res = SubString::new(b"BCABCDAABCD", b"ABCDABCDABCDABCD", 6);
 
@@ -265,4 +415,98 @@ mod test {
         assert_eq!(false, res.is_substring_at(6, 9));
         assert_eq!(true, res.is_substring_at(6, 8));
     }
+
+    #[test]
+    fn test_memory_usage() {
+        let s1 = b"ABCD".repeat(250);
+        let s2 = b"ACBD".repeat(240);
+        let delta = 60;
+        let res = SubString::new(&s1, &s2, delta);
+
+        // One bit per precomputed result instead of one byte
+        let bools = s1.len() * (2 * delta + 1);
+        assert_eq!(bools.div_ceil(64) * 8, res.memory_usage());
+        assert!(res.memory_usage() * 7 < bools);
+    }
+
+    /// Leftmost embedding of the shorter tail, by a linear scan
+    fn leftmost(s1: &[u8], s2: &[u8], i: usize, j: usize) -> Vec<(usize, usize)> {
+        let swap = s1.len() - i > s2.len() - j;
+        let (short, long, i, j) = if swap { (s2, s1, j, i) } else { (s1, s2, i, j) };
+
+        let mut positions = j..long.len();
+        (i..short.len())
+            .map(|p1| {
+                let p2 = positions.find(|&p| long[p] == short[p1]).unwrap();
+                if swap {
+                    (p2, p1)
+                } else {
+                    (p1, p2)
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_embedding() {
+        let s1 = b"ACBBABCBCACBACBCBBABBCAC";
+        let s2 = b"ABCBABAAABACBABCABCA";
+        let res = SubString::new(s1, s2, 8);
+
+        for i in 0..=s1.len() {
+            for j in i.saturating_sub(8)..=(i + 8).min(s2.len()) {
+                let embedding = res.embedding_at(s1, s2, i, j);
+                assert_eq!(res.is_substring_at(i, j), embedding.is_some());
+
+                if let Some(embedding) = embedding {
+                    let expected = (s1.len() - i).min(s2.len() - j);
+                    assert_eq!(expected, embedding.len());
+                    for w in embedding.windows(2) {
+                        assert!(w[0].0 < w[1].0 && w[0].1 < w[1].1);
+                    }
+                    for &(p1, p2) in &embedding {
+                        assert!(p1 >= i && p2 >= j);
+                        assert_eq!(s1[p1], s2[p2]);
+                    }
+                    assert_eq!(leftmost(s1, s2, i, j), embedding);
+                }
+            }
+        }
+
+        // Greedy leftmost embedding of "BC" in "BCABC"
+        let res = SubString::new(b"BCABC", b"BC", 3);
+        assert_eq!(
+            Some(vec![(0, 0), (1, 1)]),
+            res.embedding_at(b"BCABC", b"BC", 0, 0)
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = SubString::try_new(b"ABCDEF", b"AB", 3).err();
+        let expected = Error::LengthDifference {
+            len1: 6,
+            len2: 2,
+            delta: 3,
+        };
+        assert_eq!(Some(expected), err);
+
+        let res = SubString::try_new(b"ABCDEF", b"AB", 4).unwrap();
+        assert_eq!(Ok(false), res.try_is_substring_at(4, 0));
+        assert_eq!(Ok(true), res.try_is_substring_at(1, 1));
+        assert_eq!(Ok(true), res.try_is_substring_at(6, 2));
+
+        let err = res.try_is_substring_at(5, 0);
+        assert_eq!(
+            Err(Error::IndexOutOfBand {
+                i: 5,
+                j: 0,
+                delta: 4
+            }),
+            err
+        );
+
+        let err = res.try_is_substring_at(1, 3);
+        assert_eq!(Err(Error::IndexOutOfBounds { i: 1, j: 3 }), err);
+    }
 }